@@ -0,0 +1,165 @@
+use std::fs;
+use std::path::Path;
+use crate::diff;
+use crate::repo;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextAttribute {
+    Set,
+    Unset,
+    Auto,
+    Unspecified,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Rule {
+    pattern: String,
+    attributes: Vec<String>,
+}
+
+fn parse_attributes(content: &str) -> Vec<Rule> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pattern = fields.next()?.to_string();
+            Some(Rule { pattern, attributes: fields.map(str::to_string).collect() })
+        })
+        .collect()
+}
+
+pub(crate) fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => glob_match(&pattern[1..], name) || (!name.is_empty() && glob_match(pattern, &name[1..])),
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    if pattern.contains('/') {
+        glob_match(pattern.trim_start_matches('/').as_bytes(), path.as_bytes())
+    } else {
+        let basename = path.rsplit('/').next().unwrap_or(path);
+        glob_match(pattern.as_bytes(), basename.as_bytes())
+    }
+}
+
+fn text_attribute_in(rules: &[Rule], path: &str) -> TextAttribute {
+    let mut text = TextAttribute::Unspecified;
+    for rule in rules.iter().filter(|rule| pattern_matches(&rule.pattern, path)) {
+        for attribute in &rule.attributes {
+            text = match attribute.as_str() {
+                "text" => TextAttribute::Set,
+                "-text" | "binary" => TextAttribute::Unset,
+                "text=auto" => TextAttribute::Auto,
+                "!text" => TextAttribute::Unspecified,
+                _ => text,
+            };
+        }
+    }
+    text
+}
+
+pub fn text_attribute(path: &Path) -> TextAttribute {
+    let Ok(work_tree) = repo::work_tree().and_then(|work_tree| Ok(fs::canonicalize(work_tree)?)) else {
+        return TextAttribute::Unspecified;
+    };
+    let absolute = match fs::canonicalize(path) {
+        Ok(absolute) => absolute,
+        Err(_) => match std::env::current_dir().and_then(fs::canonicalize) {
+            Ok(current_dir) => current_dir.join(path),
+            Err(_) => return TextAttribute::Unspecified,
+        },
+    };
+    let Ok(relative) = absolute.strip_prefix(&work_tree) else {
+        return TextAttribute::Unspecified;
+    };
+    let rules = fs::read_to_string(work_tree.join(".gitattributes"))
+        .map(|content| parse_attributes(&content))
+        .unwrap_or_default();
+    text_attribute_in(&rules, &relative.to_string_lossy().replace('\\', "/"))
+}
+
+pub fn normalize_line_endings(content: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(content.len());
+    for (position, &byte) in content.iter().enumerate() {
+        if byte == b'\r' && content.get(position + 1) == Some(&b'\n') {
+            continue;
+        }
+        normalized.push(byte);
+    }
+    normalized
+}
+
+pub fn clean(text: TextAttribute, content: Vec<u8>) -> Vec<u8> {
+    match text {
+        TextAttribute::Set => normalize_line_endings(&content),
+        TextAttribute::Auto if !diff::is_binary(&content) => normalize_line_endings(&content),
+        _ => content,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_attributes() {
+        let content = "# comment\n*.txt text\n\n*.png binary -diff\n";
+        let expected_rules = vec![
+            Rule { pattern: "*.txt".to_string(), attributes: vec!["text".to_string()] },
+            Rule { pattern: "*.png".to_string(), attributes: vec!["binary".to_string(), "-diff".to_string()] },
+        ];
+
+        assert_eq!(parse_attributes(content), expected_rules);
+    }
+
+    #[test]
+    fn text_attribute_matches_basename_patterns() {
+        let rules = parse_attributes("*.txt text\n*.bin -text\n");
+
+        assert_eq!(text_attribute_in(&rules, "docs/readme.txt"), TextAttribute::Set);
+        assert_eq!(text_attribute_in(&rules, "data/blob.bin"), TextAttribute::Unset);
+        assert_eq!(text_attribute_in(&rules, "main.rs"), TextAttribute::Unspecified);
+    }
+
+    #[test]
+    fn text_attribute_later_rules_win() {
+        let rules = parse_attributes("* text=auto\nvendor/*.txt -text\n");
+
+        assert_eq!(text_attribute_in(&rules, "notes.txt"), TextAttribute::Auto);
+        assert_eq!(text_attribute_in(&rules, "vendor/notes.txt"), TextAttribute::Unset);
+    }
+
+    #[test]
+    fn test_normalize_line_endings() {
+        let content = b"one\r\ntwo\rthree\r\n";
+        let expected_content = b"one\ntwo\rthree\n";
+
+        assert_eq!(normalize_line_endings(content), expected_content);
+    }
+
+    #[test]
+    fn clean_normalizes_crlf_for_text_files() {
+        assert_eq!(clean(TextAttribute::Set, b"a\r\nb\r\n".to_vec()), b"a\nb\n");
+        assert_eq!(clean(TextAttribute::Auto, b"a\r\nb\r\n".to_vec()), b"a\nb\n");
+    }
+
+    #[test]
+    fn clean_keeps_crlf_without_text_attribute() {
+        assert_eq!(clean(TextAttribute::Unset, b"a\r\nb\r\n".to_vec()), b"a\r\nb\r\n");
+        assert_eq!(clean(TextAttribute::Unspecified, b"a\r\nb\r\n".to_vec()), b"a\r\nb\r\n");
+    }
+
+    #[test]
+    fn clean_keeps_binary_content_under_text_auto() {
+        let content = b"\0PNG\r\n".to_vec();
+
+        assert_eq!(clean(TextAttribute::Auto, content.clone()), content);
+    }
+}
@@ -0,0 +1,156 @@
+use std::path::Path;
+use thiserror::Error;
+use crate::catfile;
+use crate::refs;
+use crate::repo;
+use crate::Verbosity;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("a branch named '{0}' already exists")]
+    BranchExists(String),
+    #[error("not a valid object name: there are no commits to branch from")]
+    NoCommits,
+    #[error("branch '{0}' not found")]
+    BranchNotFound(String),
+    #[error("cannot delete branch '{0}' used by the current worktree")]
+    CheckedOut(String),
+}
+
+fn format_branches(branches: &[String], current: Option<&str>) -> String {
+    let mut output = String::new();
+    for branch in branches {
+        let marker = if Some(branch.as_str()) == current { '*' } else { ' ' };
+        let name = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+        output.push_str(&format!("{marker} {name}\n"));
+    }
+    output
+}
+
+fn create_branch_in(git_dir: &Path, name: &str) -> anyhow::Result<()> {
+    let ref_name = format!("refs/heads/{name}");
+    if refs::resolve_ref_in(git_dir, &ref_name).is_ok() {
+        return Err(Error::BranchExists(name.to_string()).into());
+    }
+    let hash = refs::resolve_ref_in(git_dir, "HEAD").map_err(|_|Error::NoCommits)?;
+    refs::update_ref_in(git_dir, &ref_name, &hash, "branch: Created from HEAD")?;
+    Ok(())
+}
+
+fn delete_branch_in(git_dir: &Path, name: &str) -> anyhow::Result<String> {
+    let ref_name = format!("refs/heads/{name}");
+    if refs::symbolic_target_in(git_dir, "HEAD")?.as_deref() == Some(ref_name.as_str()) {
+        return Err(Error::CheckedOut(name.to_string()).into());
+    }
+    if refs::resolve_ref_in(git_dir, &ref_name).is_err() {
+        return Err(Error::BranchNotFound(name.to_string()).into());
+    }
+    refs::delete_ref_in(git_dir, &ref_name)
+}
+
+pub fn branch(name: Option<&str>, delete: bool, verbosity: Verbosity) -> anyhow::Result<()> {
+    let git_dir = repo::git_dir()?;
+    match name {
+        Some(name) if delete => {
+            let hash = delete_branch_in(&git_dir, name)?;
+            if !verbosity.is_quiet() {
+                println!("Deleted branch {name} (was {}).", catfile::abbreviate(&hash, 7));
+            }
+            Ok(())
+        }
+        Some(name) => create_branch_in(&git_dir, name),
+        None => {
+            let branches = refs::list_refs_in(&git_dir, "refs/heads")?;
+            let current = refs::symbolic_target_in(&git_dir, "HEAD")?;
+            print!("{}", format_branches(&branches, current.as_deref()));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const HASH: &str = "ab3e94d6b7ec38af9d3dee5b424a13a292742c4c";
+
+    fn git_dir_with_head(name: &str, branch_hash: Option<&str>) -> PathBuf {
+        let git_dir = std::env::temp_dir().join(name).join(".git");
+        let _ = fs::remove_dir_all(&git_dir);
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        if let Some(hash) = branch_hash {
+            fs::write(git_dir.join("refs/heads/main"), format!("{hash}\n")).unwrap();
+        }
+        git_dir
+    }
+
+    #[test]
+    fn format_branches_marks_current_branch() {
+        let branches = vec!["refs/heads/dev".to_string(), "refs/heads/main".to_string()];
+        let expected_output = "  dev\n* main\n";
+
+        let output = format_branches(&branches, Some("refs/heads/main"));
+        assert_eq!(output, expected_output);
+    }
+
+    #[test]
+    fn create_branch_points_at_head_commit() -> anyhow::Result<()> {
+        let git_dir = git_dir_with_head("create_branch_head_commit", Some(HASH));
+
+        create_branch_in(&git_dir, "feature/nested")?;
+        assert_eq!(refs::resolve_ref_in(&git_dir, "feature/nested")?, HASH);
+        Ok(())
+    }
+
+    #[test]
+    fn create_branch_is_error_when_branch_exists() {
+        let git_dir = git_dir_with_head("create_branch_exists", Some(HASH));
+
+        let actual_result = create_branch_in(&git_dir, "main");
+        let error = actual_result.unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error, Error::BranchExists("main".to_string()));
+    }
+
+    #[test]
+    fn create_branch_is_error_when_branch_is_only_packed() {
+        let git_dir = git_dir_with_head("create_branch_packed_exists", Some(HASH));
+        fs::write(git_dir.join("packed-refs"), format!("{HASH} refs/heads/dev\n")).unwrap();
+
+        let actual_result = create_branch_in(&git_dir, "dev");
+        let error = actual_result.unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error, Error::BranchExists("dev".to_string()));
+        assert!(!git_dir.join("refs/heads/dev").exists());
+    }
+
+    #[test]
+    fn create_branch_is_error_without_commits() {
+        let git_dir = git_dir_with_head("create_branch_no_commits", None);
+
+        let actual_result = create_branch_in(&git_dir, "dev");
+        let error = actual_result.unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error, Error::NoCommits);
+    }
+
+    #[test]
+    fn delete_branch_removes_ref() -> anyhow::Result<()> {
+        let git_dir = git_dir_with_head("delete_branch_ref", Some(HASH));
+        create_branch_in(&git_dir, "dev")?;
+
+        assert_eq!(delete_branch_in(&git_dir, "dev")?, HASH);
+        assert!(!git_dir.join("refs/heads/dev").exists());
+        let error = delete_branch_in(&git_dir, "dev").unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error, Error::BranchNotFound("dev".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn delete_branch_is_error_with_current_branch() {
+        let git_dir = git_dir_with_head("delete_branch_current", Some(HASH));
+
+        let error = delete_branch_in(&git_dir, "main").unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error, Error::CheckedOut("main".to_string()));
+    }
+}
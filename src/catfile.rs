@@ -1,8 +1,172 @@
 use std::fs;
 use std::io;
-use flate2::read::ZlibDecoder;
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use flate2::Compression;
+use flate2::read::{DeflateDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use anyhow::Context;
 use thiserror::Error;
 
+use crate::attributes;
+use crate::attributes::TextAttribute;
+use crate::config;
+use crate::pack;
+use crate::refs;
+use crate::repo;
+use crate::tree;
+
+const MIN_ABBREV: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectType {
+    Blob,
+    Tree,
+    Commit,
+    Tag,
+}
+
+impl ObjectType {
+    pub fn from_bytes(bytes: &[u8]) -> Result<ObjectType, Error> {
+        match bytes {
+            b"blob" => Ok(ObjectType::Blob),
+            b"tree" => Ok(ObjectType::Tree),
+            b"commit" => Ok(ObjectType::Commit),
+            b"tag" => Ok(ObjectType::Tag),
+            _ => Err(Error::UnknownObjectType(String::from_utf8_lossy(bytes).into_owned())),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectType::Blob => "blob",
+            ObjectType::Tree => "tree",
+            ObjectType::Commit => "commit",
+            ObjectType::Tag => "tag",
+        }
+    }
+}
+
+impl std::fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ObjectFormat {
+    Sha1,
+    Sha256,
+}
+
+impl ObjectFormat {
+    pub fn current() -> ObjectFormat {
+        repo::git_dir()
+            .map(|git_dir| ObjectFormat::of(&git_dir))
+            .unwrap_or(ObjectFormat::Sha1)
+    }
+
+    pub fn of(git_dir: &Path) -> ObjectFormat {
+        fs::read_to_string(git_dir.join("config"))
+            .map(|config| ObjectFormat::from_config(&config))
+            .unwrap_or(ObjectFormat::Sha1)
+    }
+
+    fn from_config(content: &str) -> ObjectFormat {
+        match config::parse(content).get("extensions.objectformat").map(String::as_str) {
+            Some("sha256") => ObjectFormat::Sha256,
+            _ => ObjectFormat::Sha1,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectFormat::Sha1 => "sha1",
+            ObjectFormat::Sha256 => "sha256",
+        }
+    }
+
+    pub fn raw_len(&self) -> usize {
+        match self {
+            ObjectFormat::Sha1 => 20,
+            ObjectFormat::Sha256 => 32,
+        }
+    }
+
+    pub fn hex_len(&self) -> usize {
+        self.raw_len() * 2
+    }
+
+    pub fn digest(&self, payload: &[u8]) -> Vec<u8> {
+        match self {
+            ObjectFormat::Sha1 => Sha1::digest(payload).to_vec(),
+            ObjectFormat::Sha256 => Sha256::digest(payload).to_vec(),
+        }
+    }
+
+    pub fn hash(&self, payload: &[u8]) -> String {
+        hex::encode(self.digest(payload))
+    }
+
+    fn hasher(&self) -> Hasher {
+        match self {
+            ObjectFormat::Sha1 => Hasher::Sha1(Sha1::new()),
+            ObjectFormat::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+}
+
+enum Hasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Sha1(hasher) => hasher.update(bytes),
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+/// Hashes everything written through it while passing the bytes on to `inner`.
+pub(crate) struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Hasher,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub(crate) fn new(inner: W, format: ObjectFormat) -> HashingWriter<W> {
+        HashingWriter { inner, hasher: format.hasher() }
+    }
+
+    pub(crate) fn finish(self) -> (W, String) {
+        (self.inner, hex::encode(self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum Error {
     #[error("malformed git object")]
@@ -11,59 +175,599 @@ pub enum Error {
     ObjectNotFound,
     #[error("invalid object hash: {0}")]
     InvalidObjectHash(String),
+    #[error("ambiguous object hash: {0}")]
+    AmbiguousObjectHash(String),
+    #[error("unknown object type: {0}")]
+    UnknownObjectType(String),
+    #[error("could not read file: {0}")]
+    UnreadableFile(String),
+    #[error("Is a directory: {0}")]
+    IsADirectory(String),
+    #[error("No such file or directory: {0}")]
+    NoSuchFile(String),
+    #[error("cannot hash both a path and --stdin")]
+    ConflictingHashInput,
+    #[error("nothing to hash: give a path or --stdin")]
+    MissingHashInput,
+}
+
+/// How much damage an object may have and still be read.
+///
+/// `Strict` rejects any object whose declared size disagrees with its
+/// content; fsck and every internal reader (commits, trees, tags, packing,
+/// grep) use it. `Lenient` returns whatever content survives, so
+/// `cat-file -p` can still show a truncated or padded object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseMode {
+    Strict,
+    Lenient,
+}
+
+/// Ends the stream at the first read error in lenient mode instead of failing.
+struct ModeReader<R> {
+    inner: R,
+    mode: ParseMode,
+}
+
+impl<R: Read> Read for ModeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inner.read(buf) {
+            Err(err) if self.mode == ParseMode::Lenient && err.kind() != io::ErrorKind::Interrupted => Ok(0),
+            result => result,
+        }
+    }
 }
 
-fn parse_blob<R: io::Read>(blob: Option<R>) -> Result<String, Error> {
-    let mut blob = blob.ok_or(Error::ObjectNotFound)?;
-    let mut header: [u8; 4] = [0; 4];
-    blob.read_exact(&mut header).map_err(|_|Error::MalformedObject)?;
-    let header = String::from_utf8(header.to_vec()).unwrap();
-    if header != "blob" {
+pub fn parse_object<R: io::Read>(object: Option<R>, mode: ParseMode) -> Result<(ObjectType, Vec<u8>), Error> {
+    let inner = object.ok_or(Error::ObjectNotFound)?;
+    let mut content = Vec::new();
+    ModeReader { inner, mode }.read_to_end(&mut content).map_err(|_|Error::MalformedObject)?;
+
+    let space = content.iter().position(|&b| b == b' ').ok_or(Error::MalformedObject)?;
+    let nul = content.iter().position(|&b| b == 0).ok_or(Error::MalformedObject)?;
+    if nul < space {
+        return Err(Error::MalformedObject);
+    }
+    let object_type = ObjectType::from_bytes(&content[..space])?;
+    let size = std::str::from_utf8(&content[space + 1..nul]).map_err(|_|Error::MalformedObject)?;
+    let size = size.parse::<usize>().map_err(|_|Error::MalformedObject)?;
+    let content = content.split_off(nul + 1);
+    if mode == ParseMode::Strict && size != content.len() {
         return Err(Error::MalformedObject);
     }
+    Ok((object_type, content))
+}
+
+fn parse_raw_object_type<R: io::Read>(object: Option<R>) -> Result<String, Error> {
+    let object = object.ok_or(Error::ObjectNotFound)?;
+    let mut object_type = Vec::new();
+    for byte in io::BufReader::new(object).bytes() {
+        match byte.map_err(|_|Error::MalformedObject)? {
+            b' ' => return String::from_utf8(object_type).map_err(|_|Error::MalformedObject),
+            0 => break,
+            byte => object_type.push(byte),
+        }
+    }
+    Err(Error::MalformedObject)
+}
+
+fn parse_object_type<R: io::Read>(object: Option<R>) -> Result<ObjectType, Error> {
+    ObjectType::from_bytes(parse_raw_object_type(object)?.as_bytes())
+}
 
-    let mut content = String::new();
-    blob.read_to_string(&mut content).map_err(|_|Error::MalformedObject)?;
-    let (size, content) = content.trim_start().split_once('\0').ok_or(Error::MalformedObject)?;
-    if size.to_string().parse::<u8>().is_err() {
+fn read_raw_header<R: BufRead>(object: &mut R) -> Result<(Vec<u8>, usize), Error> {
+    let mut header = Vec::new();
+    object.read_until(0, &mut header).map_err(|_|Error::MalformedObject)?;
+    if header.pop() != Some(0) {
         return Err(Error::MalformedObject);
     }
-    Ok(content.to_string())
+    let space = header.iter().position(|&b| b == b' ').ok_or(Error::MalformedObject)?;
+    let size = std::str::from_utf8(&header[space + 1..]).map_err(|_|Error::MalformedObject)?;
+    let size = size.parse::<usize>().map_err(|_|Error::MalformedObject)?;
+    header.truncate(space);
+    Ok((header, size))
+}
+
+fn read_header<R: BufRead>(object: &mut R) -> Result<(ObjectType, usize), Error> {
+    let (object_type, size) = read_raw_header(object)?;
+    Ok((ObjectType::from_bytes(&object_type)?, size))
+}
+
+fn parse_object_size<R: io::Read>(object: Option<R>, allow_unknown_type: bool) -> Result<usize, Error> {
+    let object = object.ok_or(Error::ObjectNotFound)?;
+    let (object_type, size) = read_raw_header(&mut io::BufReader::new(object))?;
+    if !allow_unknown_type {
+        ObjectType::from_bytes(&object_type)?;
+    }
+    Ok(size)
+}
+
+fn stream_object<R: io::Read, W: Write>(
+    object: Option<R>,
+    out: &mut W,
+    format: ObjectFormat,
+    mode: ParseMode,
+) -> anyhow::Result<()> {
+    let inner = object.ok_or(Error::ObjectNotFound)?;
+    let mut object = io::BufReader::new(ModeReader { inner, mode });
+    let (object_type, size) = read_header(&mut object)?;
+    if object_type == ObjectType::Tree {
+        let mut content = Vec::with_capacity(size);
+        object.read_to_end(&mut content)?;
+        if mode == ParseMode::Strict && content.len() != size {
+            return Err(Error::MalformedObject.into());
+        }
+        for entry in tree::parse_tree(&content, format)? {
+            writeln!(out, "{}", tree::format_entry(&entry, false))?;
+        }
+        return Ok(());
+    }
+    let copied = io::copy(&mut object, out)?;
+    if mode == ParseMode::Strict && copied != size as u64 {
+        return Err(Error::MalformedObject.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn parse_blob<R: io::Read>(blob: Option<R>) -> Result<Vec<u8>, Error> {
+    match parse_object(blob, ParseMode::Strict)? {
+        (ObjectType::Blob, content) => Ok(content),
+        _ => Err(Error::MalformedObject),
+    }
 }
 
-fn parse_object_path_from_hash(hash: &str) -> Result<String, Error> {
-    if hash.chars().count() == 40 && hash.chars().all(char::is_alphanumeric) {
+fn object_path_in(git_dir: &Path, hash: &str) -> Result<PathBuf, Error> {
+    let hex_len = ObjectFormat::of(git_dir).hex_len();
+    if hash.len() == hex_len && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        let hash = hash.to_ascii_lowercase();
         let (dir, filename) = hash.split_at(2);
-        return Ok(format!("./.git/objects/{dir}/{filename}"));
+        return Ok(git_dir.join("objects").join(dir).join(filename));
     }
     Err(Error::InvalidObjectHash(hash.to_string()))
 }
 
+pub fn object_path(hash: &str) -> anyhow::Result<PathBuf> {
+    Ok(object_path_in(&repo::git_dir()?, hash)?)
+}
+
+fn find_object_by_prefix(objects_dir: &Path, prefix: &str, format: ObjectFormat) -> Result<String, Error> {
+    let prefix = prefix.to_ascii_lowercase();
+    let (dir, rest) = prefix.split_at(2);
+    let mut matches: Vec<String> = fs::read_dir(objects_dir.join(dir))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|filename| filename.len() == format.hex_len() - 2 && filename.starts_with(rest))
+        .map(|filename| format!("{dir}{filename}"))
+        .chain(pack::packed_hashes(objects_dir, format).into_iter().filter(|hash| hash.starts_with(&prefix)))
+        .collect();
+    matches.sort();
+    matches.dedup();
+    match matches.as_slice() {
+        [hash] => Ok(hash.clone()),
+        [] => Err(Error::ObjectNotFound),
+        _ => Err(Error::AmbiguousObjectHash(prefix.to_string())),
+    }
+}
+
+pub(crate) fn resolve_hash_in(git_dir: &Path, hash: &str) -> Result<String, Error> {
+    let format = ObjectFormat::of(git_dir);
+    let is_hex = hash.chars().all(|c| c.is_ascii_hexdigit());
+    match hash.len() {
+        len if is_hex && len == format.hex_len() => Ok(hash.to_ascii_lowercase()),
+        len if is_hex && (4..format.hex_len()).contains(&len) => {
+            find_object_by_prefix(&git_dir.join("objects"), hash, format)
+        }
+        _ => Err(Error::InvalidObjectHash(hash.to_string())),
+    }
+}
+
+/// Resolves a full or abbreviated hash, falling back to revision syntax
+/// (`HEAD`, `main`, `v1^{}`, `HEAD:path`) for anything else.
+pub fn resolve_hash(hash: &str) -> anyhow::Result<String> {
+    match resolve_hash_in(&repo::git_dir()?, hash) {
+        Err(Error::InvalidObjectHash(_)) => refs::rev_parse(hash),
+        Err(Error::ObjectNotFound) => refs::rev_parse(hash).map_err(|_|Error::ObjectNotFound.into()),
+        result => Ok(result?),
+    }
+}
+
+fn has_zlib_header(header: &[u8]) -> bool {
+    match header {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+fn decompress<R: BufRead + 'static>(mut reader: R) -> io::Result<Box<dyn Read>> {
+    if has_zlib_header(reader.fill_buf()?) {
+        Ok(Box::new(ZlibDecoder::new(reader)))
+    } else {
+        Ok(Box::new(DeflateDecoder::new(reader)))
+    }
+}
+
+fn open_loose_object(path: &Path) -> io::Result<Box<dyn Read>> {
+    decompress(io::BufReader::new(fs::File::open(path)?))
+}
+
+pub(crate) fn open_object_in(git_dir: &Path, hash: &str) -> anyhow::Result<Option<Box<dyn Read>>> {
+    let hash = resolve_hash_in(git_dir, hash)?;
+    if let Ok(object) = open_loose_object(&object_path_in(git_dir, &hash)?) {
+        return Ok(Some(object));
+    }
+    let packed = pack::find_packed_object(&git_dir.join("objects"), &hash, ObjectFormat::of(git_dir))?;
+    Ok(packed.map(|(object_type, content)| {
+        Box::new(io::Cursor::new(object_payload(object_type, &content))) as Box<dyn Read>
+    }))
+}
+
+pub fn open_object(hash: &str) -> anyhow::Result<Option<Box<dyn Read>>> {
+    let hash = resolve_hash(hash)?;
+    open_object_in(&repo::git_dir()?, &hash)
+}
+
+fn abbreviate_in(objects_dir: &Path, hash: &str, n: usize, format: ObjectFormat) -> String {
+    let min_len = n.clamp(MIN_ABBREV, hash.len().max(MIN_ABBREV));
+    (min_len..hash.len())
+        .map(|len| &hash[..len])
+        .find(|prefix| !matches!(find_object_by_prefix(objects_dir, prefix, format), Err(Error::AmbiguousObjectHash(_))))
+        .unwrap_or(hash)
+        .to_string()
+}
+
+pub fn abbreviate(hash: &str, n: usize) -> String {
+    match repo::git_dir() {
+        Ok(git_dir) => abbreviate_in(&git_dir.join("objects"), hash, n, ObjectFormat::of(&git_dir)),
+        Err(_) => hash[..n.min(hash.len())].to_string(),
+    }
+}
+
 pub fn cat_file(hash: &str) -> anyhow::Result<()> {
-    let path = parse_object_path_from_hash(hash)?;
-    let blob = fs::File::open(path).map(ZlibDecoder::new).ok();
-    let content = parse_blob(blob)?;
-    print!("{content}");
+    stream_object(open_object(hash)?, &mut io::stdout().lock(), ObjectFormat::current(), ParseMode::Lenient)
+}
+
+pub(crate) fn read_loose_object(path: &Path) -> Result<(ObjectType, Vec<u8>), Error> {
+    parse_object(open_loose_object(path).ok(), ParseMode::Strict)
+}
+
+pub(crate) fn read_object_in(git_dir: &Path, hash: &str, mode: ParseMode) -> anyhow::Result<(ObjectType, Vec<u8>)> {
+    Ok(parse_object(open_object_in(git_dir, hash)?, mode)?)
+}
+
+pub fn read_object(hash: &str, mode: ParseMode) -> anyhow::Result<(ObjectType, Vec<u8>)> {
+    Ok(parse_object(open_object(hash)?, mode)?)
+}
+
+pub fn read_blob(hash: &str) -> anyhow::Result<Vec<u8>> {
+    match read_object(hash, ParseMode::Strict)? {
+        (ObjectType::Blob, content) => Ok(content),
+        _ => Err(Error::MalformedObject.into()),
+    }
+}
+
+fn write_batch_entry<W: Write>(input: &str, out: &mut W, contents: bool) -> anyhow::Result<()> {
+    let hash = match resolve_hash(input) {
+        Ok(hash) => hash,
+        Err(err) if matches!(err.downcast_ref(), Some(Error::AmbiguousObjectHash(_))) => {
+            return Ok(writeln!(out, "{input} ambiguous")?);
+        }
+        Err(_) => return Ok(writeln!(out, "{input} missing")?),
+    };
+    if !contents {
+        let (Ok(object_type), Ok(size)) = (object_type(&hash), object_size(&hash, false)) else {
+            return Ok(writeln!(out, "{input} missing")?);
+        };
+        return Ok(writeln!(out, "{hash} {object_type} {size}")?);
+    }
+    let Ok((object_type, content)) = read_object(&hash, ParseMode::Strict) else {
+        return Ok(writeln!(out, "{input} missing")?);
+    };
+    writeln!(out, "{hash} {object_type} {}", content.len())?;
+    out.write_all(&content)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+pub fn cat_file_batch<R: BufRead, W: Write>(input: R, out: &mut W, contents: bool) -> anyhow::Result<()> {
+    for line in input.lines() {
+        write_batch_entry(line?.trim(), out, contents)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn is_valid_object<R: io::Read>(object: Option<R>) -> bool {
+    parse_object(object, ParseMode::Strict).is_ok()
+}
+
+pub fn object_exists(hash: &str) -> bool {
+    open_object(hash).map(is_valid_object).unwrap_or(false)
+}
+
+pub(crate) fn object_type_in(git_dir: &Path, hash: &str) -> anyhow::Result<ObjectType> {
+    Ok(parse_object_type(open_object_in(git_dir, hash)?)?)
+}
+
+pub fn object_type(hash: &str) -> anyhow::Result<ObjectType> {
+    Ok(parse_object_type(open_object(hash)?)?)
+}
+
+pub fn object_type_name(hash: &str, allow_unknown_type: bool) -> anyhow::Result<String> {
+    if allow_unknown_type {
+        return Ok(parse_raw_object_type(open_object(hash)?)?);
+    }
+    Ok(object_type(hash)?.to_string())
+}
+
+pub fn object_size(hash: &str, allow_unknown_type: bool) -> anyhow::Result<usize> {
+    Ok(parse_object_size(open_object(hash)?, allow_unknown_type)?)
+}
+
+pub fn object_payload(object_type: ObjectType, content: &[u8]) -> Vec<u8> {
+    let mut payload = format!("{object_type} {}\0", content.len()).into_bytes();
+    payload.extend_from_slice(content);
+    payload
+}
+
+fn blob_payload(content: &[u8]) -> Vec<u8> {
+    object_payload(ObjectType::Blob, content)
+}
+
+pub fn hash_payload(payload: &[u8]) -> String {
+    ObjectFormat::current().hash(payload)
+}
+
+fn apply_filters(path: &str, content: Vec<u8>) -> Vec<u8> {
+    attributes::clean(attributes::text_attribute(Path::new(path)), content)
+}
+
+fn read_payload(path: &str, object_type: ObjectType, filter_path: Option<&str>) -> Result<Vec<u8>, Error> {
+    let content = fs::read(path).map_err(|_|Error::UnreadableFile(path.to_string()))?;
+    let content = match filter_path {
+        Some(filter_path) if object_type == ObjectType::Blob => apply_filters(filter_path, content),
+        _ => content,
+    };
+    Ok(object_payload(object_type, &content))
+}
+
+#[cfg(unix)]
+fn symlink_target(path: &Path) -> io::Result<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(fs::read_link(path)?.as_os_str().as_bytes().to_vec())
+}
+
+#[cfg(not(unix))]
+fn symlink_target(path: &Path) -> io::Result<Vec<u8>> {
+    Ok(fs::read_link(path)?.to_string_lossy().into_owned().into_bytes())
+}
+
+/// Reads a worktree file as `add` stores it: symlinks as their target and
+/// regular files with their `.gitattributes` line-ending filter applied.
+pub fn read_worktree_file(path: &Path) -> io::Result<Vec<u8>> {
+    if fs::symlink_metadata(path)?.file_type().is_symlink() {
+        symlink_target(path)
+    } else {
+        Ok(attributes::clean(attributes::text_attribute(path), fs::read(path)?))
+    }
+}
+
+pub fn hash_file(path: &str) -> Result<String, Error> {
+    let content = read_worktree_file(Path::new(path)).map_err(|_|Error::UnreadableFile(path.to_string()))?;
+    Ok(hash_payload(&blob_payload(&content)))
+}
+
+fn compression_from_config(content: &str) -> Option<Compression> {
+    let values = config::parse(content);
+    let level = values.get("core.loosecompression").or_else(|| values.get("core.compression"))?;
+    match level.parse::<i32>().ok()? {
+        level @ 0..=9 => Some(Compression::new(level as u32)),
+        _ => None,
+    }
+}
+
+fn configured_compression_in(git_dir: &Path) -> Compression {
+    fs::read_to_string(git_dir.join("config"))
+        .ok()
+        .and_then(|config| compression_from_config(&config))
+        .unwrap_or_default()
+}
+
+fn configured_compression() -> Compression {
+    repo::git_dir().map(|git_dir| configured_compression_in(&git_dir)).unwrap_or_default()
+}
+
+fn compress(payload: &[u8], level: Compression) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), level);
+    encoder.write_all(payload)?;
+    encoder.finish()
+}
+
+fn install_object(temp_path: &Path, object_path: &Path) -> io::Result<()> {
+    if object_path.exists() {
+        return fs::remove_file(temp_path);
+    }
+    if let Some(directory) = object_path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    fs::rename(temp_path, object_path)
+}
+
+fn install_new_object<F>(object_path: &Path, write: F) -> anyhow::Result<()>
+where
+    F: FnOnce(fs::File) -> anyhow::Result<()>,
+{
+    if object_path.exists() {
+        return Ok(());
+    }
+    let directory = object_path.parent().ok_or(Error::InvalidObjectHash(object_path.display().to_string()))?;
+    fs::create_dir_all(directory)?;
+    let temp_path = temp_object_path(directory);
+    let written = fs::File::create(&temp_path)
+        .map_err(anyhow::Error::from)
+        .and_then(write)
+        .and_then(|()| Ok(install_object(&temp_path, object_path)?));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written
+}
+
+fn write_object_in(git_dir: &Path, hash: &str, payload: &[u8], level: Compression) -> anyhow::Result<()> {
+    install_new_object(&object_path_in(git_dir, hash)?, |mut temp| {
+        temp.write_all(&compress(payload, level)?)?;
+        Ok(temp.sync_all()?)
+    })
+}
+
+fn write_object(hash: &str, payload: &[u8], level: Compression) -> anyhow::Result<()> {
+    write_object_in(&repo::git_dir()?, hash, payload, level)
+}
+
+pub(crate) fn write_object_content_in(git_dir: &Path, object_type: ObjectType, content: &[u8]) -> anyhow::Result<String> {
+    let payload = object_payload(object_type, content);
+    let hash = ObjectFormat::of(git_dir).hash(&payload);
+    write_object_in(git_dir, &hash, &payload, configured_compression_in(git_dir))?;
+    Ok(hash)
+}
+
+pub fn write_object_content(object_type: ObjectType, content: &[u8]) -> anyhow::Result<String> {
+    write_object_content_in(&repo::git_dir()?, object_type, content)
+}
+
+fn temp_object_path(dir: &Path) -> PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    dir.join(format!("tmp_obj_{}_{nanos:08x}", std::process::id()))
+}
+
+fn copy_object_content<R: Read, W: Write>(path: &str, file: &mut R, size: u64, out: &mut W) -> anyhow::Result<()> {
+    if io::copy(file, out)? != size {
+        return Err(Error::UnreadableFile(path.to_string()).into());
+    }
+    Ok(())
+}
+
+fn stream_content_in<R: Read>(git_dir: &Path, path: &str, content: &mut R, size: u64, object_type: ObjectType, level: Compression) -> anyhow::Result<String> {
+    let objects_dir = git_dir.join("objects");
+    fs::create_dir_all(&objects_dir)?;
+    let temp_path = temp_object_path(&objects_dir);
+    let written = fs::File::create(&temp_path).map_err(anyhow::Error::from).and_then(|temp| {
+        let mut writer = HashingWriter::new(ZlibEncoder::new(temp, level), ObjectFormat::of(git_dir));
+        writer.write_all(format!("{object_type} {size}\0").as_bytes())?;
+        copy_object_content(path, content, size, &mut writer)?;
+        let (encoder, hash) = writer.finish();
+        encoder.finish()?.sync_all()?;
+        install_object(&temp_path, &object_path_in(git_dir, &hash)?)?;
+        Ok(hash)
+    });
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written
+}
+
+fn stream_file(path: &str, object_type: ObjectType, write: Option<Compression>) -> anyhow::Result<String> {
+    let mut file = fs::File::open(path).map_err(|_|Error::UnreadableFile(path.to_string()))?;
+    let size = file.metadata()?.len();
+    if let Some(level) = write {
+        return stream_content_in(&repo::git_dir()?, path, &mut file, size, object_type, level);
+    }
+    let mut writer = HashingWriter::new(io::sink(), ObjectFormat::current());
+    writer.write_all(format!("{object_type} {size}\0").as_bytes())?;
+    copy_object_content(path, &mut file, size, &mut writer)?;
+    Ok(writer.finish().1)
+}
+
+fn check_hash_input(paths: &[String], stdin: bool) -> Result<(), Error> {
+    match (paths.is_empty(), stdin) {
+        (false, true) => Err(Error::ConflictingHashInput),
+        (true, false) => Err(Error::MissingHashInput),
+        _ => Ok(()),
+    }
+}
+
+fn store_payload(payload: &[u8], write: Option<Compression>) -> anyhow::Result<String> {
+    let hash = hash_payload(payload);
+    if let Some(level) = write {
+        write_object(&hash, payload, level)?;
+    }
+    Ok(hash)
+}
+
+pub struct HashOptions<'a> {
+    pub object_type: &'a str,
+    pub write: bool,
+    pub filters: bool,
+    pub path: Option<&'a str>,
+    pub compression: Option<u32>,
+}
+
+fn check_hash_path(path: &str) -> Result<(), Error> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => Err(Error::IsADirectory(path.to_string())),
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Err(Error::NoSuchFile(path.to_string())),
+        Err(_) => Err(Error::UnreadableFile(path.to_string())),
+    }
+}
+
+fn hash_path(path: &str, object_type: ObjectType, options: &HashOptions, write: Option<Compression>) -> anyhow::Result<String> {
+    check_hash_path(path)?;
+    let filter_path = options.filters.then(|| options.path.unwrap_or(path));
+    let filtered = object_type == ObjectType::Blob
+        && filter_path.is_some_and(|filter_path| matches!(attributes::text_attribute(Path::new(filter_path)), TextAttribute::Set | TextAttribute::Auto));
+    let hash = if filtered {
+        read_payload(path, object_type, filter_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|payload| store_payload(&payload, write))
+    } else {
+        stream_file(path, object_type, write)
+    };
+    hash.with_context(|| format!("failed to hash {path}"))
+}
+
+fn hash_paths_from<R: BufRead, W: Write>(input: R, out: &mut W, object_type: ObjectType, options: &HashOptions, write: Option<Compression>) -> anyhow::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        let path = line.trim_end();
+        if path.is_empty() {
+            continue;
+        }
+        writeln!(out, "{}", hash_path(path, object_type, options, write)?)?;
+        out.flush()?;
+    }
     Ok(())
 }
 
-pub fn hash_object(_path: &str) -> anyhow::Result<()> {
-    let hash = "ac136066947976e9f5ae7cc6bdccac22d0fc0f6f"; 
-    let object_path = parse_object_path_from_hash(hash)?;
-    if let Err(err) = fs::write(object_path.clone(), "") {
-        if err.kind() == std::io::ErrorKind::NotFound {
-            let (directory, _filename) = object_path.split_at(17);
-            fs::create_dir(directory)?;
-            fs::write(object_path, "")?;
+pub fn hash_object(paths: &[String], stdin: bool, stdin_paths: bool, options: &HashOptions) -> anyhow::Result<()> {
+    check_hash_input(paths, stdin || stdin_paths)?;
+    let object_type = ObjectType::from_bytes(options.object_type.as_bytes())?;
+    let write = options.write.then(|| options.compression.map(Compression::new).unwrap_or_else(configured_compression));
+    if stdin_paths {
+        return hash_paths_from(io::stdin().lock(), &mut io::stdout().lock(), object_type, options, write);
+    }
+    if stdin {
+        let mut content = Vec::new();
+        io::stdin().read_to_end(&mut content)?;
+        if let (true, Some(path), ObjectType::Blob) = (options.filters, options.path, object_type) {
+            content = apply_filters(path, content);
         }
+        println!("{}", store_payload(&object_payload(object_type, &content), write)?);
+    }
+    for path in paths {
+        println!("{}", hash_path(path, object_type, options, write)?);
     }
-    println!("{hash}");
     Ok(())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::env;
 
     #[test]
     fn parse_blob_succeeds_with_good_blob() -> Result<(), Error> {
@@ -71,7 +775,7 @@ mod test {
         let expected_content = "abcd123";
 
         let content = parse_blob(Some(blob_content))?;
-        assert_eq!(content, expected_content);
+        assert_eq!(content, expected_content.as_bytes());
         Ok(())
     }
 
@@ -103,60 +807,837 @@ mod test {
     }
 
     #[test]
-    fn parse_blob_is_error_with_nonexistent_blob() {
-        let blob_content: Option<&[u8]> = None;
-        let expected_error = Err(Error::ObjectNotFound);
+    fn parse_blob_succeeds_with_300_byte_blob() -> Result<(), Error> {
+        let expected_content = "a".repeat(300);
+        let blob_content = format!("blob 300\0{expected_content}");
 
-        let actual_result = parse_blob(blob_content);
-        assert_eq!(actual_result, expected_error);
+        let content = parse_blob(Some(blob_content.as_bytes()))?;
+        assert_eq!(content, expected_content.as_bytes());
+        Ok(())
     }
 
     #[test]
-    fn test_parse_object_path_from_hash() -> Result<(), Error> {
-        let object_hash = "a1b2c3d4e5f6g7h8i9j0a1b2c3d4e5f6g7h8i9j0";
-        let expected_path = "./.git/objects/a1/b2c3d4e5f6g7h8i9j0a1b2c3d4e5f6g7h8i9j0";
+    fn parse_blob_succeeds_with_100000_byte_blob() -> Result<(), Error> {
+        let expected_content = "abcd123\n".repeat(12500);
+        let blob_content = format!("blob 100000\0{expected_content}");
 
-        let path = parse_object_path_from_hash(object_hash)?;
-        assert_eq!(path, expected_path);
+        let content = parse_blob(Some(blob_content.as_bytes()))?;
+        assert_eq!(content, expected_content.as_bytes());
         Ok(())
     }
-    
+
     #[test]
-    fn test_parse_object_path_from_hash_with_invalid_chars() {
-        let object_hash = "a1b2c3d4e5f6g7h8i9j/a1b2c3d4e5f6g7h8i9j/";
-        let expected_error = Err(Error::InvalidObjectHash(object_hash.to_string()));
+    fn parse_blob_is_error_with_truncated_content() {
+        let blob_content = "blob 300\0abcd123".as_bytes();
+        let expected_error = Err(Error::MalformedObject);
 
-        let actual_result = parse_object_path_from_hash(object_hash);
-        println!("{:?}", actual_result);
+        let actual_result = parse_blob(Some(blob_content));
         assert_eq!(actual_result, expected_error);
     }
 
     #[test]
-    fn test_parse_object_path_from_hash_with_short_length() {
-        let object_hash = "1234567890";
-        let expected_error = Err(Error::InvalidObjectHash(object_hash.to_string()));
+    fn parse_blob_succeeds_with_binary_blob() -> Result<(), Error> {
+        let blob_content = b"blob 3\0\xff\x00\xfe";
+        let expected_content = [0xFF, 0x00, 0xFE];
 
-        let actual_result = parse_object_path_from_hash(object_hash);
-        assert_eq!(actual_result, expected_error);
+        let content = parse_blob(Some(blob_content.as_slice()))?;
+        assert_eq!(content, expected_content);
+        Ok(())
     }
 
     #[test]
-    fn test_parse_object_path_from_hash_with_long_length() {
-        let object_hash = "a1b2c3d4e5f6g7h8i9j0a1b2c3d4e5f6g7h8i9j01234567890";
-        let expected_error = Err(Error::InvalidObjectHash(object_hash.to_string()));
+    fn binary_blob_round_trips_through_compression() -> Result<(), Error> {
+        let expected_content = [0xFF, 0x00, 0xFE];
+        let payload = blob_payload(&expected_content);
 
-        let actual_result = parse_object_path_from_hash(object_hash);
-        assert_eq!(actual_result, expected_error);
+        let compressed = compress(&payload, Compression::default()).unwrap();
+        let content = parse_blob(Some(ZlibDecoder::new(compressed.as_slice())))?;
+        assert_eq!(content, expected_content);
+        Ok(())
     }
 
     #[test]
-    fn hash_object_from_valid_path() -> Result<(), Error>{
-        let path = "strawberry.txt";
+    fn parse_blob_is_error_with_tree_object() {
+        let blob_content = "tree 7\0abcd123".as_bytes();
+        let expected_error = Err(Error::MalformedObject);
+
+        let actual_result = parse_blob(Some(blob_content));
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn parse_object_reads_type_up_to_first_space() -> Result<(), Error> {
+        let object_content = "commit 7\0abcd123".as_bytes();
+        let expected_type = ObjectType::Commit;
+
+        let (object_type, _content) = parse_object(Some(object_content), ParseMode::Strict)?;
+        assert_eq!(object_type, expected_type);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_object_is_error_with_unknown_type() {
+        let object_content = "blobby 7\0abcd123".as_bytes();
+        let expected_error = Err(Error::UnknownObjectType("blobby".to_string()));
+
+        let actual_result = parse_object(Some(object_content), ParseMode::Strict);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn object_type_round_trips_through_as_str() -> Result<(), Error> {
+        for object_type in [ObjectType::Blob, ObjectType::Tree, ObjectType::Commit, ObjectType::Tag] {
+            assert_eq!(ObjectType::from_bytes(object_type.as_str().as_bytes())?, object_type);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parse_object_is_error_with_no_space() {
+        let object_content = "blob7\0abcd 123".as_bytes();
+        let expected_error = Err(Error::MalformedObject);
+
+        let actual_result = parse_object(Some(object_content), ParseMode::Strict);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn test_parse_object_type() -> Result<(), Error> {
+        let object_content = "tree 7\0abcd123".as_bytes();
+        let expected_type = ObjectType::Tree;
+
+        let object_type = parse_object_type(Some(object_content))?;
+        assert_eq!(object_type, expected_type);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_object_type_is_error_with_no_space() {
+        let object_content = "tree7\0abcd123".as_bytes();
+        let expected_error = Err(Error::MalformedObject);
+
+        let actual_result = parse_object_type(Some(object_content));
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn parse_object_type_is_error_with_nonexistent_object() {
+        let object_content: Option<&[u8]> = None;
+        let expected_error = Err(Error::ObjectNotFound);
+
+        let actual_result = parse_object_type(object_content);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn parse_raw_object_type_allows_unknown_type() -> Result<(), Error> {
+        let object_content = "blobby 3\0abc".as_bytes();
+
+        assert_eq!(parse_raw_object_type(Some(object_content))?, "blobby");
+        assert_eq!(parse_object_type(Some(object_content)), Err(Error::UnknownObjectType("blobby".to_string())));
+        assert_eq!(parse_object_size(Some(object_content), true)?, 3);
+        assert_eq!(parse_object_size(Some(object_content), false), Err(Error::UnknownObjectType("blobby".to_string())));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_object_size_matches_content_length() -> Result<(), Error> {
+        let content = "abcd123\n".repeat(40);
+        let object_content = blob_payload(content.as_bytes());
+
+        let size = parse_object_size(Some(object_content.as_slice()), false)?;
+        assert_eq!(size, content.len());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_object_size_is_error_with_empty_size() {
+        let object_content = "blob \0abcd123".as_bytes();
+        let expected_error = Err(Error::MalformedObject);
+
+        let actual_result = parse_object_size(Some(object_content), false);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn parse_object_size_is_error_with_non_numeric_size() {
+        let object_content = "blob 7a\0abcd123".as_bytes();
+        let expected_error = Err(Error::MalformedObject);
+
+        let actual_result = parse_object_size(Some(object_content), false);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn stream_object_copies_blob_content() -> anyhow::Result<()> {
+        let expected_content = "abcd123\n".repeat(12500);
+        let compressed = compress(&blob_payload(expected_content.as_bytes()), Compression::default())?;
+        let mut out = Vec::new();
+
+        stream_object(Some(ZlibDecoder::new(compressed.as_slice())), &mut out, ObjectFormat::Sha1, ParseMode::Strict)?;
+        assert_eq!(out, expected_content.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn stream_object_writes_blob_bytes_exactly() -> anyhow::Result<()> {
+        for content in [&b"no trailing newline"[..], b"trailing newline\n", b"two\n\n", b"\xff\x00binary", b""] {
+            let payload = blob_payload(content);
+            let mut out = Vec::new();
+
+            stream_object(Some(payload.as_slice()), &mut out, ObjectFormat::Sha1, ParseMode::Strict)?;
+            assert_eq!(out, content);
+            assert_eq!(parse_object_size(Some(payload.as_slice()), false)?, out.len());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn stream_object_is_error_with_truncated_content() {
+        let blob_content = "blob 300\0abcd123".as_bytes();
+        let mut out = Vec::new();
+
+        let actual_result = stream_object(Some(blob_content), &mut out, ObjectFormat::Sha1, ParseMode::Strict);
+        let error = actual_result.unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error, Error::MalformedObject);
+    }
+
+    fn truncated_blob() -> anyhow::Result<Vec<u8>> {
+        let mut compressed = compress(&blob_payload(&b"abcd123\n".repeat(500)), Compression::none())?;
+        compressed.truncate(compressed.len() / 2);
+        Ok(compressed)
+    }
+
+    #[test]
+    fn parse_object_strict_rejects_truncated_object() -> anyhow::Result<()> {
+        let compressed = truncated_blob()?;
+
+        let actual_result = parse_object(Some(ZlibDecoder::new(compressed.as_slice())), ParseMode::Strict);
+        assert_eq!(actual_result, Err(Error::MalformedObject));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_object_lenient_returns_surviving_content() -> anyhow::Result<()> {
+        let compressed = truncated_blob()?;
+
+        let (object_type, content) = parse_object(Some(ZlibDecoder::new(compressed.as_slice())), ParseMode::Lenient)?;
+        assert_eq!(object_type, ObjectType::Blob);
+        assert!(!content.is_empty() && content.len() < 4000);
+        assert!(b"abcd123\n".repeat(500).starts_with(&content));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_object_strict_rejects_trailing_bytes() {
+        let actual_result = parse_object(Some("blob 3\0abcd123".as_bytes()), ParseMode::Strict);
+        assert_eq!(actual_result, Err(Error::MalformedObject));
+
+        let lenient = parse_object(Some("blob 3\0abcd123".as_bytes()), ParseMode::Lenient);
+        assert_eq!(lenient, Ok((ObjectType::Blob, b"abcd123".to_vec())));
+    }
+
+    #[test]
+    fn stream_object_lenient_prints_truncated_content() -> anyhow::Result<()> {
+        let mut out = Vec::new();
+
+        stream_object(Some("blob 300\0abcd123".as_bytes()), &mut out, ObjectFormat::Sha1, ParseMode::Lenient)?;
+        assert_eq!(out, b"abcd123");
+        Ok(())
+    }
+
+    #[test]
+    fn stream_object_lists_tree_entries() -> anyhow::Result<()> {
+        let mut content = b"100644 a.txt\0".to_vec();
+        content.extend_from_slice(&[0xab; 20]);
+        content.extend_from_slice(b"40000 src\0");
+        content.extend_from_slice(&[0x01; 20]);
+        let payload = object_payload(ObjectType::Tree, &content);
+        let expected_output = format!("100644 blob {}\ta.txt\n040000 tree {}\tsrc\n", "ab".repeat(20), "01".repeat(20));
+        let mut out = Vec::new();
+
+        stream_object(Some(payload.as_slice()), &mut out, ObjectFormat::Sha1, ParseMode::Strict)?;
+        assert_eq!(String::from_utf8(out)?, expected_output);
+        Ok(())
+    }
+
+    #[test]
+    fn stream_object_prints_commit_text() -> anyhow::Result<()> {
+        let content = b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\nmessage\n";
+        let payload = object_payload(ObjectType::Commit, content);
+        let mut out = Vec::new();
+
+        stream_object(Some(payload.as_slice()), &mut out, ObjectFormat::Sha1, ParseMode::Strict)?;
+        assert_eq!(out, content);
+        Ok(())
+    }
+
+    #[test]
+    fn decompress_falls_back_to_raw_deflate() -> anyhow::Result<()> {
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&blob_payload(b"raw deflate"))?;
+        let compressed = encoder.finish()?;
+
+        let content = parse_blob(Some(decompress(io::Cursor::new(compressed))?))?;
+        assert_eq!(content, b"raw deflate");
+        Ok(())
+    }
+
+    #[test]
+    fn decompress_prefers_zlib() -> anyhow::Result<()> {
+        let compressed = compress(&blob_payload(b"zlib"), Compression::default())?;
+
+        assert!(has_zlib_header(&compressed));
+        assert_eq!(parse_blob(Some(decompress(io::Cursor::new(compressed))?))?, b"zlib");
+        Ok(())
+    }
+
+    #[test]
+    fn is_valid_object_with_good_object() {
+        let compressed = compress(&blob_payload("abcd123".as_bytes()), Compression::default()).unwrap();
+
+        assert!(is_valid_object(Some(ZlibDecoder::new(compressed.as_slice()))));
+    }
+
+    #[test]
+    fn is_valid_object_with_corrupt_object() {
+        let compressed = compress("blob 300\0abcd123".as_bytes(), Compression::default()).unwrap();
+
+        assert!(!is_valid_object(Some(ZlibDecoder::new(compressed.as_slice()))));
+        assert!(!is_valid_object(Some(ZlibDecoder::new("not zlib data".as_bytes()))));
+        assert!(!is_valid_object::<&[u8]>(None));
+    }
+
+    #[test]
+    fn parse_blob_is_error_with_under_declared_size() {
+        let blob_content = "blob 5\0abcdefghij".as_bytes();
+        let expected_error = Err(Error::MalformedObject);
+
+        let actual_result = parse_blob(Some(blob_content));
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn parse_blob_is_error_with_over_declared_size() {
+        let blob_content = "blob 15\0abcdefghij".as_bytes();
+        let expected_error = Err(Error::MalformedObject);
+
+        let actual_result = parse_blob(Some(blob_content));
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn parse_blob_counts_size_in_bytes() -> Result<(), Error> {
+        let expected_content = "héllo";
+        let blob_content = format!("blob 6\0{expected_content}");
+
+        let content = parse_blob(Some(blob_content.as_bytes()))?;
+        assert_eq!(content, expected_content.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_blob_is_error_with_char_count_as_size() {
+        let blob_content = "blob 5\0héllo".as_bytes();
+        let expected_error = Err(Error::MalformedObject);
+
+        let actual_result = parse_blob(Some(blob_content));
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn parse_blob_is_error_with_nonexistent_blob() {
+        let blob_content: Option<&[u8]> = None;
+        let expected_error = Err(Error::ObjectNotFound);
+
+        let actual_result = parse_blob(blob_content);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn test_object_path() -> Result<(), Error> {
+        let object_hash = "a1b2c3d4e5f6a7b8c9d0a1b2c3d4e5f6a7b8c9d0";
+        let expected_path = PathBuf::from("./.git/objects/a1/b2c3d4e5f6a7b8c9d0a1b2c3d4e5f6a7b8c9d0");
+
+        let path = object_path_in(Path::new("./.git"), object_hash)?;
+        assert_eq!(path, expected_path);
+        Ok(())
+    }
+    
+    #[test]
+    fn object_path_lowercases_hash() -> Result<(), Error> {
+        let object_hash = "AC136066947976E9F5AE7CC6BDCCAC22D0FC0F6F";
+        let expected_path = PathBuf::from("./.git/objects/ac/136066947976e9f5ae7cc6bdccac22d0fc0f6f");
+
+        let path = object_path_in(Path::new("./.git"), object_hash)?;
+        assert_eq!(path, expected_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_path_with_non_hex_letters() {
+        let object_hash = "g1b2c3d4e5f6a7b8c9d0a1b2c3d4e5f6a7b8c9dz";
+        let expected_error = Err(Error::InvalidObjectHash(object_hash.to_string()));
+
+        let actual_result = object_path_in(Path::new("./.git"), object_hash);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    fn objects_dir_with(name: &str, hashes: &[&str]) -> PathBuf {
+        let objects_dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&objects_dir);
+        for hash in hashes {
+            let (dir, filename) = hash.split_at(2);
+            fs::create_dir_all(objects_dir.join(dir)).unwrap();
+            fs::write(objects_dir.join(dir).join(filename), "").unwrap();
+        }
+        objects_dir
+    }
+
+    #[test]
+    fn read_loose_object_returns_type_and_content() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join("read_loose_object_commit");
+        fs::write(&path, compress(&object_payload(ObjectType::Commit, b"tree abc\n"), Compression::default())?)?;
+
+        assert_eq!(read_loose_object(&path)?, (ObjectType::Commit, b"tree abc\n".to_vec()));
+        assert_eq!(read_loose_object(Path::new("does/not/exist")), Err(Error::ObjectNotFound));
+        Ok(())
+    }
+
+    #[test]
+    fn compression_from_config_reads_core_levels() {
+        assert_eq!(compression_from_config("[core]\n\tcompression = 9\n"), Some(Compression::new(9)));
+        assert_eq!(compression_from_config("[core]\n\tcompression = 9\n\tlooseCompression = 0\n"), Some(Compression::new(0)));
+        assert_eq!(compression_from_config("[pack]\n\tcompression = 9\n"), None);
+        assert_eq!(compression_from_config("[core]\n\tcompression = -1\n"), None);
+    }
+
+    #[test]
+    fn compressed_objects_decompress_at_every_level() -> Result<(), Error> {
+        let content = "what is up, doc?\n".repeat(100);
+        for level in [0, 1, 6, 9] {
+            let compressed = compress(&blob_payload(content.as_bytes()), Compression::new(level)).unwrap();
+
+            assert_eq!(parse_blob(Some(ZlibDecoder::new(compressed.as_slice())))?, content.as_bytes());
+        }
+        Ok(())
+    }
+
+    fn cat_file_batch_in_empty_repo(name: &str, input: &str, contents: bool) -> anyhow::Result<Vec<u8>> {
+        let _lock = repo::ENV_LOCK.lock().unwrap();
+        let git_dir = env::temp_dir().join(name).join(".git");
+        let _ = fs::remove_dir_all(&git_dir);
+        fs::create_dir_all(git_dir.join("objects"))?;
+        fs::create_dir_all(git_dir.join("refs/heads"))?;
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n")?;
+        env::set_var("GIT_DIR", &git_dir);
+
+        let mut out = Vec::new();
+        let result = cat_file_batch(input.as_bytes(), &mut out, contents);
+        env::remove_var("GIT_DIR");
+        result.map(|()| out)
+    }
+
+    #[test]
+    fn cat_file_batch_reports_missing_objects() -> anyhow::Result<()> {
+        let input = "not-a-hash\n0000000000000000000000000000000000000000\n";
+        let out = cat_file_batch_in_empty_repo("cat_file_batch_missing", input, true)?;
+
+        assert_eq!(out, b"not-a-hash missing\n0000000000000000000000000000000000000000 missing\n");
+        Ok(())
+    }
+
+    #[test]
+    fn cat_file_batch_check_reports_missing_objects() -> anyhow::Result<()> {
+        let out = cat_file_batch_in_empty_repo("cat_file_batch_check_missing", "  not-a-hash  \n", false)?;
+
+        assert_eq!(out, b"not-a-hash missing\n");
+        Ok(())
+    }
+
+    #[test]
+    fn abbreviate_lengthens_ambiguous_prefix() {
+        let objects_dir = objects_dir_with("abbreviate_objects", &[
+            "abcdef0123456789abcdef0123456789abcdef01",
+            "abcdef9876543210abcdef0123456789abcdef01",
+        ]);
+
+        let abbreviated = abbreviate_in(&objects_dir, "abcdef0123456789abcdef0123456789abcdef01", 4, ObjectFormat::Sha1);
+        assert_eq!(abbreviated, "abcdef0");
+        let abbreviated = abbreviate_in(&objects_dir, "abcdef0123456789abcdef0123456789abcdef01", 10, ObjectFormat::Sha1);
+        assert_eq!(abbreviated, "abcdef0123");
+    }
+
+    #[test]
+    fn abbreviate_clamps_to_minimum_length() {
+        let objects_dir = objects_dir_with("abbreviate_minimum_objects", &[]);
+
+        let abbreviated = abbreviate_in(&objects_dir, "abcdef0123456789abcdef0123456789abcdef01", 1, ObjectFormat::Sha1);
+        assert_eq!(abbreviated, "abcd");
+    }
+
+    #[test]
+    fn find_object_by_prefix_finds_unique_object() -> Result<(), Error> {
         let expected_hash = "ac136066947976e9f5ae7cc6bdccac22d0fc0f6f";
+        let objects_dir = objects_dir_with("find_object_by_prefix_unique", &[
+            expected_hash,
+            "ac246066947976e9f5ae7cc6bdccac22d0fc0f6f",
+        ]);
+
+        let hash = find_object_by_prefix(&objects_dir, "AC1360", ObjectFormat::Sha1)?;
+        assert_eq!(hash, expected_hash);
+        Ok(())
+    }
+
+    #[test]
+    fn find_object_by_prefix_is_error_with_ambiguous_prefix() {
+        let objects_dir = objects_dir_with("find_object_by_prefix_ambiguous", &[
+            "ac136066947976e9f5ae7cc6bdccac22d0fc0f6f",
+            "ac136166947976e9f5ae7cc6bdccac22d0fc0f6f",
+        ]);
+        let expected_error = Err(Error::AmbiguousObjectHash("ac13".to_string()));
+
+        let actual_result = find_object_by_prefix(&objects_dir, "ac13", ObjectFormat::Sha1);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn find_object_by_prefix_is_error_with_no_match() {
+        let objects_dir = objects_dir_with("find_object_by_prefix_no_match", &[
+            "ac136066947976e9f5ae7cc6bdccac22d0fc0f6f",
+        ]);
+        let expected_error = Err(Error::ObjectNotFound);
+
+        let actual_result = find_object_by_prefix(&objects_dir, "ac2", ObjectFormat::Sha1);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn resolve_hash_is_error_with_too_short_prefix() {
+        let object_hash = "ac1";
+        let expected_error = Err(Error::InvalidObjectHash(object_hash.to_string()));
+
+        let actual_result = resolve_hash_in(Path::new("./.git"), object_hash);
+        assert_eq!(actual_result, expected_error);
+    }
 
-        let actual_result = hash_file(path)?;
+    #[test]
+    fn resolve_hash_accepts_ref_names() -> anyhow::Result<()> {
+        let _lock = repo::ENV_LOCK.lock().unwrap();
+        let git_dir = std::env::temp_dir().join("resolve_hash_ref_names").join(".git");
+        let _ = fs::remove_dir_all(&git_dir);
+        fs::create_dir_all(git_dir.join("objects"))?;
+        fs::create_dir_all(git_dir.join("refs/heads"))?;
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n")?;
+        std::env::set_var("GIT_DIR", &git_dir);
+
+        let result = (|| {
+            let blob = write_object_content(ObjectType::Blob, b"by name")?;
+            fs::write(git_dir.join("refs/heads/main"), format!("{blob}\n"))?;
+            Ok::<_, anyhow::Error>((blob, resolve_hash("HEAD")?, resolve_hash("main")?, read_blob("main")?))
+        })();
+        std::env::remove_var("GIT_DIR");
+
+        let (blob, head, main, content) = result?;
+        assert_eq!((head, main), (blob.clone(), blob));
+        assert_eq!(content, b"by name");
+        Ok(())
+    }
+
+    #[test]
+    fn object_path_parent_is_object_directory() -> Result<(), Error> {
+        let object_hash = "ac136066947976e9f5ae7cc6bdccac22d0fc0f6f";
+        let expected_directory = Path::new("./.git/objects/ac");
+
+        let path = object_path_in(Path::new("./.git"), object_hash)?;
+        assert_eq!(path.parent(), Some(expected_directory));
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_path_with_invalid_chars() {
+        let object_hash = "a1b2c3d4e5f6g7h8i9j/a1b2c3d4e5f6g7h8i9j/";
+        let expected_error = Err(Error::InvalidObjectHash(object_hash.to_string()));
+
+        let actual_result = object_path_in(Path::new("./.git"), object_hash);
+        println!("{:?}", actual_result);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn test_object_path_with_short_length() {
+        let object_hash = "1234567890";
+        let expected_error = Err(Error::InvalidObjectHash(object_hash.to_string()));
+
+        let actual_result = object_path_in(Path::new("./.git"), object_hash);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn test_object_path_with_long_length() {
+        let object_hash = "a1b2c3d4e5f6g7h8i9j0a1b2c3d4e5f6g7h8i9j01234567890";
+        let expected_error = Err(Error::InvalidObjectHash(object_hash.to_string()));
+
+        let actual_result = object_path_in(Path::new("./.git"), object_hash);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn hash_object_from_valid_path() -> Result<(), Error>{
+        let path = std::env::temp_dir().join("hash_object_from_valid_path.txt");
+        fs::write(&path, "strawberry banana\n").unwrap();
+        let expected_hash = "1a135cd95c7698cd0b49cb3c959cc9b5202cd4b6";
+
+        let actual_result = hash_file(path.to_str().unwrap())?;
         assert_eq!(actual_result, expected_hash);
         Ok(())
     }
-}
 
+    #[test]
+    fn hash_object_from_nonexistent_path() {
+        let path = "does/not/exist.txt";
+        let expected_error = Err(Error::UnreadableFile(path.to_string()));
+
+        let actual_result = hash_file(path);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn check_hash_input_is_error_with_path_and_stdin() {
+        let paths = vec!["a.txt".to_string()];
+        let expected_error = Err(Error::ConflictingHashInput);
+
+        let actual_result = check_hash_input(&paths, true);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn check_hash_input_is_error_with_no_input() {
+        let expected_error = Err(Error::MissingHashInput);
+
+        let actual_result = check_hash_input(&[], false);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn check_hash_input_accepts_multiple_paths() {
+        let paths = vec!["a.txt".to_string(), "b.txt".to_string()];
+
+        assert_eq!(check_hash_input(&paths, false), Ok(()));
+    }
+
+    #[test]
+    fn hash_object_is_error_with_unknown_type() {
+        let options = HashOptions { object_type: "blub", write: false, filters: true, path: None, compression: None };
+
+        let actual_result = hash_object(&[], true, false, &options);
+
+        let error = actual_result.unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error, Error::UnknownObjectType("blub".to_string()));
+    }
+
+    #[test]
+    fn read_payload_uses_object_type_header() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join("read_payload_tree");
+        fs::write(&path, b"")?;
+        let expected_hash = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+        let payload = read_payload(&path.to_string_lossy(), ObjectType::Tree, None)?;
+        assert_eq!(ObjectFormat::Sha1.hash(&payload), expected_hash);
+        Ok(())
+    }
+
+    #[test]
+    fn hash_paths_from_hashes_each_listed_path() -> anyhow::Result<()> {
+        let dir = env::temp_dir().join("hash_paths_from_listed");
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("a.txt"), "hello world")?;
+        fs::write(dir.join("b.txt"), "what is up, doc?")?;
+        let input = format!("{}\n\n{}  \n", dir.join("a.txt").display(), dir.join("b.txt").display());
+        let options = HashOptions { object_type: "blob", write: false, filters: false, path: None, compression: None };
+        let mut out = Vec::new();
+
+        hash_paths_from(input.as_bytes(), &mut out, ObjectType::Blob, &options, None)?;
+        assert_eq!(String::from_utf8(out)?, "95d09f2b10159347eece71399a7e2e907ea3df4f\nbd9dbf5aae1a3862dd1526723246b20206e5fc37\n");
+        Ok(())
+    }
+
+    #[test]
+    fn hash_object_reports_failing_path() {
+        let paths = vec!["does/not/exist.txt".to_string()];
+
+        let actual_result = hash_object(&paths, false, false, &HashOptions { object_type: "blob", write: false, filters: true, path: None, compression: None });
+        let message = format!("{:#}", actual_result.unwrap_err());
+        assert!(message.contains("does/not/exist.txt"));
+    }
+
+    #[test]
+    fn hash_object_is_error_with_directory_or_missing_path() {
+        let directory = std::env::temp_dir().to_string_lossy().into_owned();
+        let options = HashOptions { object_type: "blob", write: false, filters: false, path: None, compression: None };
+
+        for (path, expected_error) in [
+            (directory.clone(), Error::IsADirectory(directory)),
+            ("does/not/exist.txt".to_string(), Error::NoSuchFile("does/not/exist.txt".to_string())),
+        ] {
+            let actual_result = hash_object(&[path], false, false, &options);
+            let error = actual_result.unwrap_err().downcast::<Error>().unwrap();
+            assert_eq!(error, expected_error);
+        }
+    }
+
+    #[test]
+    fn test_blob_payload() {
+        let content = "hello world".as_bytes();
+        let expected_payload = "blob 11\0hello world".as_bytes();
+
+        let payload = blob_payload(content);
+        assert_eq!(payload, expected_payload);
+    }
+
+    #[test]
+    fn compressed_payload_decompresses_to_blob() -> Result<(), Error> {
+        let payload = blob_payload("abcd123".as_bytes());
+        let expected_content = "abcd123";
+
+        let compressed = compress(&payload, Compression::default()).unwrap();
+        let content = parse_blob(Some(ZlibDecoder::new(compressed.as_slice())))?;
+        assert_eq!(content, expected_content.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_payload() {
+        let payload = blob_payload("hello world".as_bytes());
+        let expected_hash = "95d09f2b10159347eece71399a7e2e907ea3df4f";
+
+        let hash = hash_payload(&payload);
+        assert_eq!(hash, expected_hash);
+    }
+
+    #[test]
+    fn test_hash_payload_with_sha256() {
+        let payload = blob_payload("hello world".as_bytes());
+        let expected_hash = "fee53a18d32820613c0527aa79be5cb30173c823a9b448fa4817767cc84c6f03";
+
+        let hash = ObjectFormat::Sha256.hash(&payload);
+        assert_eq!(hash, expected_hash);
+    }
+
+    #[test]
+    fn object_format_from_config_with_sha256_extension() {
+        let config = "[core]\n\trepositoryformatversion = 1\n[extensions]\n\tobjectformat = sha256\n";
+
+        assert_eq!(ObjectFormat::from_config(config), ObjectFormat::Sha256);
+    }
+
+    #[test]
+    fn object_format_from_config_defaults_to_sha1() {
+        let config = "[core]\n\trepositoryformatversion = 0\n";
+
+        assert_eq!(ObjectFormat::from_config(config), ObjectFormat::Sha1);
+    }
+
+    #[test]
+    fn find_object_by_prefix_with_sha256() -> Result<(), Error> {
+        let expected_hash = "fee53a18d32820613c0527aa79be5cb30173c823a9b448fa4817767cc84c6f03";
+        let objects_dir = objects_dir_with("find_object_by_prefix_sha256", &[
+            expected_hash,
+            "ac136066947976e9f5ae7cc6bdccac22d0fc0f6f",
+        ]);
+
+        let hash = find_object_by_prefix(&objects_dir, "fee53a", ObjectFormat::Sha256)?;
+        assert_eq!(hash, expected_hash);
+        Ok(())
+    }
+
+    #[test]
+    fn read_object_uses_git_dir_from_environment() -> anyhow::Result<()> {
+        let _lock = repo::ENV_LOCK.lock().unwrap();
+        let git_dir = env::temp_dir().join("read_object_git_dir_env");
+        let _ = fs::remove_dir_all(&git_dir);
+        fs::create_dir_all(git_dir.join("objects"))?;
+        env::set_var("GIT_DIR", &git_dir);
+
+        let result = write_object_content(ObjectType::Blob, b"from GIT_DIR\n")
+            .and_then(|hash| Ok((object_path_in(&git_dir, &hash)?, read_object(&hash, ParseMode::Strict)?)));
+        env::remove_var("GIT_DIR");
+
+        let (path, (object_type, content)) = result?;
+        assert!(path.is_file());
+        assert_eq!(object_type, ObjectType::Blob);
+        assert_eq!(content, b"from GIT_DIR\n");
+        Ok(())
+    }
+
+    struct ForwardOnly<'a>(&'a [u8]);
+
+    impl Read for ForwardOnly<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn stream_content_reads_input_once() -> anyhow::Result<()> {
+        let git_dir = env::temp_dir().join("stream_content_once").join(".git");
+        let _ = fs::remove_dir_all(&git_dir);
+        let content = "what is up, doc?\n".repeat(1000);
+
+        let hash = stream_content_in(&git_dir, "doc.txt", &mut ForwardOnly(content.as_bytes()), content.len() as u64, ObjectType::Blob, Compression::default())?;
+        assert_eq!(hash, ObjectFormat::Sha1.hash(&blob_payload(content.as_bytes())));
+        assert_eq!(read_loose_object(&object_path_in(&git_dir, &hash)?)?, (ObjectType::Blob, content.into_bytes()));
+        assert_eq!(fs::read_dir(git_dir.join("objects"))?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn hashing_writer_hashes_and_passes_bytes_through() -> io::Result<()> {
+        let payload = blob_payload(b"what is up, doc?");
+
+        for format in [ObjectFormat::Sha1, ObjectFormat::Sha256] {
+            let mut writer = HashingWriter::new(Vec::new(), format);
+            writer.write_all(&payload[..5])?;
+            writer.write_all(&payload[5..])?;
+            let (written, hash) = writer.finish();
+            assert_eq!(written, payload);
+            assert_eq!(hash, format.hash(&payload));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn write_object_content_skips_existing_object() -> anyhow::Result<()> {
+        let _lock = repo::ENV_LOCK.lock().unwrap();
+        let git_dir = env::temp_dir().join("write_object_existing");
+        let _ = fs::remove_dir_all(&git_dir);
+        let hash = "bd9dbf5aae1a3862dd1526723246b20206e5fc37";
+        let path = object_path_in(&git_dir, hash)?;
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, b"already here")?;
+        env::set_var("GIT_DIR", &git_dir);
+
+        let result = write_object_content(ObjectType::Blob, b"what is up, doc?");
+        env::remove_var("GIT_DIR");
+
+        assert_eq!(result?, hash);
+        assert_eq!(fs::read(&path)?, b"already here");
+        assert_eq!(fs::read_dir(path.parent().unwrap())?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn write_object_content_creates_missing_object_directories() -> anyhow::Result<()> {
+        let _lock = repo::ENV_LOCK.lock().unwrap();
+        let git_dir = env::temp_dir().join("write_object_missing_dirs").join(".git");
+        let _ = fs::remove_dir_all(git_dir.parent().unwrap());
+        env::set_var("GIT_DIR", &git_dir);
+
+        let result = write_object_content(ObjectType::Blob, b"what is up, doc?");
+        env::remove_var("GIT_DIR");
+
+        let hash = result?;
+        assert!(object_path_in(&git_dir, &hash)?.is_file());
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+use crate::catfile;
+use crate::catfile::ObjectType;
+use crate::commit;
+use crate::index;
+use crate::index::IndexEntry;
+use crate::refs;
+use crate::repo;
+use crate::tag;
+use crate::tree;
+use crate::Verbosity;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("Your local changes to the following files would be overwritten by checkout:\n{}\nPlease commit your changes or stash them before you switch branches.", format_paths(.0))]
+    LocalChanges(Vec<String>),
+}
+
+fn format_paths(paths: &[String]) -> String {
+    paths.iter().map(|path| format!("\t{path}")).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(unix)]
+fn apply_mode(path: &Path, mode: &str) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    let current = permissions.mode();
+    let updated = match mode {
+        "100755" => current | (current & 0o444) >> 2,
+        _ => current & !0o111,
+    };
+    if updated != current {
+        permissions.set_mode(updated);
+        fs::set_permissions(path, permissions)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn apply_mode(_path: &Path, _mode: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write_symlink(path: &Path, target: &[u8]) -> std::io::Result<()> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path)?;
+    }
+    std::os::unix::fs::symlink(OsStr::from_bytes(target), path)
+}
+
+#[cfg(not(unix))]
+fn write_symlink(path: &Path, target: &[u8]) -> std::io::Result<()> {
+    fs::write(path, target)
+}
+
+fn is_unchanged(staged: &IndexEntry, entry: &tree::TreeEntry) -> bool {
+    hex::encode(&staged.hash) == entry.hash && format!("{:o}", staged.mode) == entry.mode
+}
+
+fn checkout_into(
+    hash: &str,
+    dir: &Path,
+    prefix: &str,
+    current: &BTreeMap<&str, &IndexEntry>,
+    entries: &mut Vec<IndexEntry>,
+) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    for entry in tree::read_tree(hash)? {
+        let path = dir.join(&entry.name);
+        let index_path = format!("{prefix}{}", entry.name);
+        if let Some(staged) = current.get(index_path.as_str()).filter(|staged| is_unchanged(staged, &entry)) {
+            entries.push((*staged).clone());
+            continue;
+        }
+        match entry.object_type() {
+            ObjectType::Tree => checkout_into(&entry.hash, &path, &format!("{index_path}/"), current, entries)?,
+            ObjectType::Commit => fs::create_dir_all(&path)?,
+            _ if entry.mode == "120000" => {
+                write_symlink(&path, &catfile::read_blob(&entry.hash)?)?;
+                entries.push(IndexEntry::from_metadata(index_path, hex::decode(&entry.hash)?, &fs::symlink_metadata(&path)?));
+            }
+            _ => {
+                if fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+                    fs::remove_file(&path)?;
+                }
+                fs::write(&path, catfile::read_blob(&entry.hash)?)?;
+                apply_mode(&path, &entry.mode)?;
+                entries.push(IndexEntry::from_metadata(index_path, hex::decode(&entry.hash)?, &fs::metadata(&path)?));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes `hash` into `dir`, leaving alone any path whose entry in `current`
+/// already matches the target so local edits to it survive.
+pub fn checkout_tree(hash: &str, dir: &Path, current: &[IndexEntry]) -> anyhow::Result<Vec<IndexEntry>> {
+    let current = current.iter().map(|entry| (entry.path.as_str(), entry)).collect();
+    let mut entries = Vec::new();
+    checkout_into(hash, dir, "", &current, &mut entries)?;
+    entries.sort_by(|a, b| a.path.as_bytes().cmp(b.path.as_bytes()));
+    Ok(entries)
+}
+
+fn blocked_paths(index: &[IndexEntry], target: &BTreeMap<String, String>, worktree: &BTreeMap<String, String>) -> Vec<String> {
+    index
+        .iter()
+        .filter_map(|entry| {
+            let staged = hex::encode(&entry.hash);
+            let modified = worktree.get(&entry.path).is_some_and(|hash| *hash != staged);
+            let replaced = target.get(&entry.path) != Some(&staged);
+            (modified && replaced).then(|| entry.path.clone())
+        })
+        .collect()
+}
+
+fn worktree_hashes(work_tree: &Path, index: &[IndexEntry]) -> BTreeMap<String, String> {
+    index
+        .iter()
+        .filter_map(|entry| {
+            let path = work_tree.join(&entry.path);
+            let hash = catfile::hash_file(path.to_str()?).ok()?;
+            Some((entry.path.clone(), hash))
+        })
+        .collect()
+}
+
+fn remove_file(work_tree: &Path, path: &str) -> anyhow::Result<()> {
+    let path = work_tree.join(path);
+    if fs::symlink_metadata(&path).is_ok() {
+        fs::remove_file(&path)?;
+    }
+    for dir in path.ancestors().skip(1).take_while(|dir| *dir != work_tree) {
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub fn checkout(target: &str, verbosity: Verbosity) -> anyhow::Result<()> {
+    let branch = refs::resolve_ref(&format!("refs/heads/{target}")).ok().map(|_| target);
+    let hash = tag::peel_tag(&refs::rev_parse(target)?)?;
+    let commit = commit::parse_commit(&hash)?;
+    let work_tree = repo::work_tree()?;
+    let old = refs::resolve_ref("HEAD").ok();
+    let from = match refs::symbolic_target("HEAD")? {
+        Some(head) => head.strip_prefix("refs/heads/").unwrap_or(&head).to_string(),
+        None => old.clone().unwrap_or_default(),
+    };
+    let message = format!("checkout: moving from {from} to {target}");
+
+    let index = index::read_index()?;
+    let target_files = tree::flatten_tree(&commit.tree)?;
+    let blocked = blocked_paths(&index, &target_files, &worktree_hashes(&work_tree, &index));
+    if !blocked.is_empty() {
+        return Err(Error::LocalChanges(blocked).into());
+    }
+
+    for entry in index.iter().filter(|entry| !target_files.contains_key(&entry.path)) {
+        remove_file(&work_tree, &entry.path)?;
+    }
+    index::write_index(&checkout_tree(&commit.tree, &work_tree, &index)?)?;
+
+    match branch {
+        Some(branch) => {
+            refs::write_symbolic_ref("HEAD", &format!("refs/heads/{branch}"))?;
+            refs::append_reflog("HEAD", old.as_deref(), &hash, &message)?;
+            if !verbosity.is_quiet() {
+                eprintln!("Switched to branch '{branch}'");
+            }
+        }
+        None => {
+            refs::update_ref("HEAD", &hash, &message)?;
+            if !verbosity.is_quiet() {
+                let subject = commit.message.lines().next().unwrap_or("");
+                eprintln!("HEAD is now at {} {subject}", &hash[..7]);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(path: &str, hash: &str) -> IndexEntry {
+        IndexEntry { path: path.to_string(), hash: hex::decode(hash).unwrap(), ..IndexEntry::default() }
+    }
+
+    fn map(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries.iter().map(|(path, hash)| (path.to_string(), hash.to_string())).collect()
+    }
+
+    #[test]
+    fn blocked_paths_reports_modified_files_that_would_change() {
+        let index = vec![entry("a.txt", "aaaa"), entry("b.txt", "bbbb"), entry("c.txt", "cccc")];
+        let target = map(&[("a.txt", "1111"), ("b.txt", "bbbb")]);
+        let worktree = map(&[("a.txt", "2222"), ("b.txt", "3333"), ("c.txt", "4444")]);
+
+        assert_eq!(blocked_paths(&index, &target, &worktree), vec!["a.txt", "c.txt"]);
+    }
+
+    #[test]
+    fn blocked_paths_ignores_clean_files() {
+        let index = vec![entry("a.txt", "aaaa")];
+        let target = map(&[("a.txt", "1111")]);
+        let worktree = map(&[("a.txt", "aaaa")]);
+
+        assert!(blocked_paths(&index, &target, &worktree).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executable_mode_round_trips() -> std::io::Result<()> {
+        let dir = std::env::temp_dir().join("executable_mode_round_trips");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("run.sh");
+        fs::write(&path, "#!/bin/sh\n")?;
+
+        apply_mode(&path, "100644")?;
+        assert_eq!(tree::file_mode(&fs::metadata(&path)?), "100644");
+        apply_mode(&path, "100755")?;
+        assert_eq!(tree::file_mode(&fs::metadata(&path)?), "100755");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_symlink_replaces_existing_file() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join("write_symlink_replaces_existing_file");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("link");
+        let _ = fs::remove_file(&path);
+        fs::write(&path, "regular file")?;
+
+        write_symlink(&path, b"../target.txt")?;
+
+        assert_eq!(fs::read_link(&path)?, Path::new("../target.txt"));
+        assert_eq!(catfile::read_worktree_file(&path)?, b"../target.txt");
+        Ok(())
+    }
+
+    #[test]
+    fn checkout_keeps_local_edits_to_paths_the_target_does_not_change() -> anyhow::Result<()> {
+        let _lock = repo::ENV_LOCK.lock().unwrap();
+        let root = std::env::temp_dir().join("checkout_keeps_local_edits");
+        let _ = fs::remove_dir_all(&root);
+        let git_dir = root.join(".git");
+        fs::create_dir_all(git_dir.join("objects"))?;
+        fs::create_dir_all(git_dir.join("refs/heads"))?;
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n")?;
+        std::env::set_var("GIT_DIR", &git_dir);
+        std::env::set_var("GIT_WORK_TREE", &root);
+
+        let result = (|| {
+            let commit_with = |a: &[u8]| -> anyhow::Result<String> {
+                let a = catfile::write_object_content(ObjectType::Blob, a)?;
+                let b = catfile::write_object_content(ObjectType::Blob, b"kept\n")?;
+                let tree = tree::mktree(format!("100644 blob {a}\ta.txt\n100644 blob {b}\tb.txt\n").as_bytes())?;
+                let signature = "Jane Doe <jane@example.com> 1700000000 +0000";
+                let content = format!("tree {tree}\nauthor {signature}\ncommitter {signature}\n\nmessage\n");
+                catfile::write_object_content(ObjectType::Commit, content.as_bytes())
+            };
+            let main = commit_with(b"one\n")?;
+            let other = commit_with(b"two\n")?;
+            fs::write(git_dir.join("refs/heads/main"), format!("{main}\n"))?;
+            fs::write(git_dir.join("refs/heads/other"), format!("{other}\n"))?;
+            index::write_index(&checkout_tree(&commit::parse_commit(&main)?.tree, &root, &[])?)?;
+            fs::write(root.join("b.txt"), "local edit\n")?;
+
+            checkout("other", Verbosity::Quiet)?;
+            Ok::<_, anyhow::Error>((fs::read_to_string(root.join("a.txt"))?, fs::read_to_string(root.join("b.txt"))?))
+        })();
+        std::env::remove_var("GIT_DIR");
+        std::env::remove_var("GIT_WORK_TREE");
+
+        assert_eq!(result?, ("two\n".to_string(), "local edit\n".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn local_changes_error_lists_paths() {
+        let error = Error::LocalChanges(vec!["a.txt".to_string(), "d/b.txt".to_string()]);
+
+        assert!(error.to_string().contains("overwritten by checkout:\n\ta.txt\n\td/b.txt\n"));
+    }
+}
@@ -0,0 +1,403 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use thiserror::Error;
+use crate::catfile::ObjectFormat;
+use crate::checkout;
+use crate::commit;
+use crate::index;
+use crate::pack;
+use crate::protocol;
+use crate::refs;
+use crate::Verbosity;
+
+const SERVICE: &str = "git-upload-pack";
+const CAPABILITIES: &[&str] = &["side-band-64k", "ofs-delta"];
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("invalid pkt-line")]
+    InvalidPktLine,
+    #[error("unexpected service advertisement")]
+    UnexpectedService,
+    #[error("remote error: {0}")]
+    Remote(String),
+    #[error("destination path '{0}' already exists and is not an empty directory")]
+    DestinationExists(String),
+    #[error("server does not support shallow clients")]
+    ShallowUnsupported,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Advertisement {
+    refs: Vec<(String, String)>,
+    capabilities: Vec<String>,
+}
+
+impl Advertisement {
+    fn capability(&self, name: &str) -> Option<&str> {
+        self.capabilities.iter().find_map(|capability| match capability.split_once('=') {
+            Some((key, value)) if key == name => Some(value),
+            None if capability == name => Some(""),
+            _ => None,
+        })
+    }
+
+    fn object_format(&self) -> ObjectFormat {
+        match self.capability("object-format") {
+            Some("sha256") => ObjectFormat::Sha256,
+            _ => ObjectFormat::Sha1,
+        }
+    }
+
+    fn head(&self) -> Option<&str> {
+        self.refs.iter().find(|(_, name)| name == "HEAD").map(|(hash, _)| hash.as_str())
+    }
+
+    fn default_branch(&self) -> Option<String> {
+        let symref = self.capabilities.iter().find_map(|capability| {
+            capability.strip_prefix("symref=HEAD:refs/heads/")
+        });
+        if let Some(branch) = symref {
+            return Some(branch.to_string());
+        }
+        let head = self.head()?;
+        self.refs
+            .iter()
+            .find(|(hash, name)| hash == head && name.starts_with("refs/heads/"))
+            .map(|(_, name)| name["refs/heads/".len()..].to_string())
+    }
+}
+
+fn read_pkt_line(data: &mut &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    protocol::read_pkt_line(data).map_err(|_|Error::InvalidPktLine)
+}
+
+fn parse_advertisement(mut body: &[u8]) -> Result<Advertisement, Error> {
+    let service = read_pkt_line(&mut body)?.ok_or(Error::UnexpectedService)?;
+    if service != format!("# service={SERVICE}\n").as_bytes() {
+        return Err(Error::UnexpectedService);
+    }
+    while read_pkt_line(&mut body)?.is_some() {}
+
+    let mut advertisement = Advertisement { refs: Vec::new(), capabilities: Vec::new() };
+    while let Some(line) = read_pkt_line(&mut body)? {
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches('\n');
+        let (reference, capabilities) = line.split_once('\0').unwrap_or((line, ""));
+        if !capabilities.is_empty() {
+            advertisement.capabilities = capabilities.split(' ').map(str::to_string).collect();
+        }
+        let (hash, name) = reference.split_once(' ').ok_or(Error::InvalidPktLine)?;
+        if name != "capabilities^{}" && !name.ends_with("^{}") {
+            advertisement.refs.push((hash.to_string(), name.to_string()));
+        }
+    }
+    Ok(advertisement)
+}
+
+fn upload_pack_request(wants: &[&str], capabilities: &[&str], depth: Option<usize>) -> io::Result<Vec<u8>> {
+    let mut request = Vec::new();
+    for (i, want) in wants.iter().enumerate() {
+        let line = if i == 0 { format!("want {want} {}\n", capabilities.join(" ")) } else { format!("want {want}\n") };
+        protocol::write_pkt_line(&mut request, line.as_bytes())?;
+    }
+    if let Some(depth) = depth {
+        protocol::write_pkt_line(&mut request, format!("deepen {depth}\n").as_bytes())?;
+    }
+    protocol::write_flush(&mut request)?;
+    protocol::write_pkt_line(&mut request, b"done\n")?;
+    Ok(request)
+}
+
+fn read_shallow_info(body: &mut &[u8]) -> Result<Vec<String>, Error> {
+    let mut shallow = Vec::new();
+    while let Some(line) = read_pkt_line(body)? {
+        let line = String::from_utf8_lossy(&line);
+        match line.trim_end().split_once(' ') {
+            Some(("shallow", hash)) => shallow.push(hash.to_string()),
+            Some(("unshallow", _)) => {}
+            _ => return Err(Error::InvalidPktLine),
+        }
+    }
+    Ok(shallow)
+}
+
+fn write_progress<W: Write>(out: &mut W, progress: &[u8], at_line_start: &mut bool) -> io::Result<()> {
+    for segment in progress.split_inclusive(|&byte| byte == b'\r' || byte == b'\n') {
+        if *at_line_start {
+            out.write_all(b"remote: ")?;
+        }
+        out.write_all(segment)?;
+        *at_line_start = segment.ends_with(b"\r") || segment.ends_with(b"\n");
+    }
+    out.flush()
+}
+
+fn demultiplex<W: Write>(mut body: &[u8], side_band: bool, progress: Option<&mut W>) -> Result<Vec<u8>, Error> {
+    loop {
+        if !side_band && body.starts_with(b"PACK") {
+            return Ok(body.to_vec());
+        }
+        let mut rest = body;
+        match read_pkt_line(&mut rest)? {
+            Some(line) if line.starts_with(b"NAK") || line.starts_with(b"ACK") => body = rest,
+            Some(line) if line.starts_with(b"ERR ") => {
+                return Err(Error::Remote(String::from_utf8_lossy(&line[4..]).trim_end().to_string()));
+            }
+            _ if side_band => break,
+            _ => return Err(Error::InvalidPktLine),
+        }
+    }
+
+    let mut pack = Vec::new();
+    let mut progress = progress;
+    let mut at_line_start = true;
+    while !body.is_empty() {
+        let Some(line) = read_pkt_line(&mut body)? else {
+            break;
+        };
+        match line.split_first() {
+            Some((1, data)) => pack.extend_from_slice(data),
+            Some((2, message)) => {
+                if let Some(out) = progress.as_deref_mut() {
+                    write_progress(out, message, &mut at_line_start).map_err(|_|Error::InvalidPktLine)?;
+                }
+            }
+            Some((3, message)) => {
+                return Err(Error::Remote(String::from_utf8_lossy(message).trim_end().to_string()));
+            }
+            _ => return Err(Error::InvalidPktLine),
+        }
+    }
+    Ok(pack)
+}
+
+fn directory_name(url: &str) -> String {
+    let name = url.trim_end_matches('/').rsplit('/').next().unwrap_or(url);
+    name.strip_suffix(".git").unwrap_or(name).to_string()
+}
+
+fn fetch_pack(url: &str, advertisement: &Advertisement, depth: Option<usize>, show_progress: bool) -> anyhow::Result<(Vec<u8>, Vec<String>)> {
+    let mut wants: Vec<&str> = advertisement
+        .refs
+        .iter()
+        .filter(|(_, name)| name.starts_with("refs/heads/") || name.starts_with("refs/tags/"))
+        .map(|(hash, _)| hash.as_str())
+        .collect();
+    wants.extend(advertisement.head());
+    wants.sort();
+    wants.dedup();
+
+    let mut capabilities: Vec<&str> = CAPABILITIES
+        .iter()
+        .copied()
+        .filter(|capability| advertisement.capability(capability).is_some())
+        .collect();
+    if !show_progress && advertisement.capability("no-progress").is_some() {
+        capabilities.push("no-progress");
+    }
+    if depth.is_some() {
+        if advertisement.capability("shallow").is_none() {
+            return Err(Error::ShallowUnsupported.into());
+        }
+        capabilities.push("shallow");
+    }
+    let response = reqwest::blocking::Client::new()
+        .post(format!("{url}/{SERVICE}"))
+        .header("Content-Type", format!("application/x-{SERVICE}-request"))
+        .header("Accept", format!("application/x-{SERVICE}-result"))
+        .body(upload_pack_request(&wants, &capabilities, depth)?)
+        .send()?
+        .error_for_status()?
+        .bytes()?;
+    let mut body: &[u8] = &response;
+    let shallow = if depth.is_some() { read_shallow_info(&mut body)? } else { Vec::new() };
+    let mut stderr = io::stderr();
+    Ok((demultiplex(body, capabilities.contains(&"side-band-64k"), show_progress.then_some(&mut stderr))?, shallow))
+}
+
+fn remote_config(url: &str, branch: Option<&str>) -> String {
+    let mut config = format!("[remote \"origin\"]\n\turl = {url}\n\tfetch = +refs/heads/*:refs/remotes/origin/*\n");
+    if let Some(branch) = branch {
+        config.push_str(&format!("[branch \"{branch}\"]\n\tremote = origin\n\tmerge = refs/heads/{branch}\n"));
+    }
+    config
+}
+
+pub fn clone(url: &str, dir: Option<&str>, depth: Option<usize>, verbosity: Verbosity) -> anyhow::Result<()> {
+    let url = url.trim_end_matches('/');
+    let dir = dir.map(str::to_string).unwrap_or_else(|| directory_name(url));
+    let root = Path::new(&dir);
+    if root.exists() && fs::read_dir(root)?.next().is_some() {
+        return Err(Error::DestinationExists(dir).into());
+    }
+    if !verbosity.is_quiet() {
+        eprintln!("Cloning into '{dir}'...");
+    }
+
+    let body = reqwest::blocking::get(format!("{url}/info/refs?service={SERVICE}"))?
+        .error_for_status()?
+        .bytes()?;
+    let advertisement = parse_advertisement(&body)?;
+    let format = advertisement.object_format();
+    let branch = advertisement.default_branch();
+
+    crate::create_repository(root, format)?;
+    env::set_current_dir(root)?;
+    env::remove_var("GIT_DIR");
+    let mut config = fs::OpenOptions::new().append(true).open(".git/config")?;
+    config.write_all(remote_config(url, branch.as_deref()).as_bytes())?;
+    if let Some(branch) = &branch {
+        refs::write_symbolic_ref("HEAD", &format!("refs/heads/{branch}"))?;
+    }
+
+    let Some(head) = advertisement.head() else {
+        eprintln!("warning: You appear to have cloned an empty repository.");
+        return Ok(());
+    };
+    let (pack_data, mut shallow) = fetch_pack(url, &advertisement, depth, !verbosity.is_quiet())?;
+    pack::store_pack(Path::new(".git/objects"), &pack_data, format, !verbosity.is_quiet())?;
+    shallow.sort();
+    shallow.dedup();
+    if !shallow.is_empty() {
+        fs::write(".git/shallow", shallow.iter().map(|hash| format!("{hash}\n")).collect::<String>())?;
+    }
+
+    let message = format!("clone: from {url}");
+    for (hash, name) in &advertisement.refs {
+        if let Some(branch) = name.strip_prefix("refs/heads/") {
+            refs::update_ref(&format!("refs/remotes/origin/{branch}"), hash, &message)?;
+        } else if name.starts_with("refs/tags/") {
+            refs::update_ref(name, hash, &message)?;
+        }
+    }
+    refs::update_head(head, &message)?;
+
+    let tree = commit::parse_commit(head)?.tree;
+    let entries = checkout::checkout_tree(&tree, Path::new("."), &[])?;
+    index::write_index(&entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pkt_line(data: &[u8]) -> Vec<u8> {
+        let mut line = Vec::new();
+        protocol::write_pkt_line(&mut line, data).unwrap();
+        line
+    }
+
+    fn sample_advertisement() -> Vec<u8> {
+        let mut body = pkt_line(b"# service=git-upload-pack\n");
+        body.extend_from_slice(b"0000");
+        body.extend(pkt_line(b"4b825dc642cb6eb9a060e54bf8d69288fbee4904 HEAD\0multi_ack side-band-64k symref=HEAD:refs/heads/trunk\n"));
+        body.extend(pkt_line(b"4b825dc642cb6eb9a060e54bf8d69288fbee4904 refs/heads/trunk\n"));
+        body.extend(pkt_line(b"ab3e94d6c3f5e2b2e0ad9cc8bfcb9d92c1d157c3 refs/tags/v1\n"));
+        body.extend(pkt_line(b"6255d5323fd5dbfa6e6d9f52f3e8c2a43fc6b4f3 refs/tags/v1^{}\n"));
+        body.extend_from_slice(b"0000");
+        body
+    }
+
+    #[test]
+    fn parse_advertisement_reads_refs_and_capabilities() -> Result<(), Error> {
+        let advertisement = parse_advertisement(&sample_advertisement())?;
+
+        assert_eq!(advertisement.refs.len(), 3);
+        assert_eq!(advertisement.head(), Some("4b825dc642cb6eb9a060e54bf8d69288fbee4904"));
+        assert_eq!(advertisement.capability("side-band-64k"), Some(""));
+        assert_eq!(advertisement.default_branch(), Some("trunk".to_string()));
+        assert_eq!(advertisement.object_format(), ObjectFormat::Sha1);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_advertisement_is_error_without_service_line() {
+        let body = pkt_line(b"4b825dc642cb6eb9a060e54bf8d69288fbee4904 HEAD\n");
+
+        assert_eq!(parse_advertisement(&body), Err(Error::UnexpectedService));
+    }
+
+    #[test]
+    fn upload_pack_request_sends_capabilities_with_first_want() -> io::Result<()> {
+        let request = upload_pack_request(&["aaaa", "bbbb"], &["ofs-delta"], None)?;
+
+        assert_eq!(request, b"0018want aaaa ofs-delta\n000ewant bbbb\n00000009done\n");
+        Ok(())
+    }
+
+    #[test]
+    fn upload_pack_request_sends_deepen_before_flush() -> io::Result<()> {
+        let request = upload_pack_request(&["aaaa"], &["shallow"], Some(1))?;
+
+        assert_eq!(request, b"0016want aaaa shallow\n000ddeepen 1\n00000009done\n");
+        Ok(())
+    }
+
+    #[test]
+    fn read_shallow_info_collects_boundary_commits() -> Result<(), Error> {
+        let mut body = pkt_line(b"shallow 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n");
+        body.extend_from_slice(b"0000");
+        body.extend(pkt_line(b"NAK\n"));
+        let mut rest = body.as_slice();
+
+        assert_eq!(read_shallow_info(&mut rest)?, ["4b825dc642cb6eb9a060e54bf8d69288fbee4904"]);
+        assert_eq!(rest, pkt_line(b"NAK\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn demultiplex_collects_pack_band() -> Result<(), Error> {
+        let mut body = pkt_line(b"NAK\n");
+        body.extend(pkt_line(b"\x01PACK"));
+        body.extend(pkt_line(b"\x02Counting objects\r"));
+        body.extend(pkt_line(b"\x01rest"));
+        body.extend_from_slice(b"0000");
+
+        let mut progress = Vec::new();
+        assert_eq!(demultiplex(&body, true, Some(&mut progress))?, b"PACKrest");
+        assert_eq!(progress, b"remote: Counting objects\r");
+        Ok(())
+    }
+
+    #[test]
+    fn demultiplex_prefixes_each_progress_line() -> Result<(), Error> {
+        let mut body = pkt_line(b"NAK\n");
+        body.extend(pkt_line(b"\x02Counting: 50%\rCounting: 100%\nCompr"));
+        body.extend(pkt_line(b"\x02essing\n"));
+        body.extend_from_slice(b"0000");
+
+        let mut progress = Vec::new();
+        demultiplex(&body, true, Some(&mut progress))?;
+        assert_eq!(progress, b"remote: Counting: 50%\rremote: Counting: 100%\nremote: Compressing\n");
+        Ok(())
+    }
+
+    #[test]
+    fn demultiplex_drops_progress_when_quiet() -> Result<(), Error> {
+        let mut body = pkt_line(b"NAK\n");
+        body.extend(pkt_line(b"\x02Counting objects\r"));
+        body.extend(pkt_line(b"\x01PACK"));
+        body.extend_from_slice(b"0000");
+
+        assert_eq!(demultiplex(&body, true, None::<&mut Vec<u8>>)?, b"PACK");
+        Ok(())
+    }
+
+    #[test]
+    fn demultiplex_reports_remote_error() {
+        let mut body = pkt_line(b"NAK\n");
+        body.extend(pkt_line(b"\x03access denied\n"));
+
+        assert_eq!(demultiplex(&body, true, None::<&mut Vec<u8>>), Err(Error::Remote("access denied".to_string())));
+    }
+
+    #[test]
+    fn test_directory_name() {
+        assert_eq!(directory_name("https://github.com/user/repo.git"), "repo");
+        assert_eq!(directory_name("https://example.com/project/"), "project");
+    }
+}
@@ -0,0 +1,75 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    Always,
+    Never,
+    #[default]
+    Auto,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+}
+
+impl Color {
+    fn code(&self) -> &'static str {
+        match self {
+            Color::Red => "31",
+            Color::Green => "32",
+            Color::Yellow => "33",
+        }
+    }
+}
+
+fn enabled_with(choice: ColorChoice, no_color: bool, terminal: bool) -> bool {
+    if no_color {
+        return false;
+    }
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => terminal,
+    }
+}
+
+pub fn enabled(choice: ColorChoice) -> bool {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    enabled_with(choice, no_color, io::stdout().is_terminal())
+}
+
+pub fn paint(text: &str, color: Color, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{text}\x1b[m", color.code())
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn enabled_with_follows_choice_and_terminal() {
+        assert!(enabled_with(ColorChoice::Always, false, false));
+        assert!(!enabled_with(ColorChoice::Never, false, true));
+        assert!(enabled_with(ColorChoice::Auto, false, true));
+        assert!(!enabled_with(ColorChoice::Auto, false, false));
+    }
+
+    #[test]
+    fn enabled_with_no_color_overrides_always() {
+        assert!(!enabled_with(ColorChoice::Always, true, true));
+    }
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(paint("abc1234", Color::Yellow, true), "\x1b[33mabc1234\x1b[m");
+        assert_eq!(paint("abc1234", Color::Yellow, false), "abc1234");
+    }
+}
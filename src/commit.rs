@@ -0,0 +1,747 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use thiserror::Error;
+use crate::catfile;
+use crate::catfile::{ObjectType, ParseMode};
+use crate::refs;
+use crate::repo;
+use crate::tag;
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("malformed commit object")]
+    MalformedCommit,
+    #[error("not a commit object: {0}")]
+    NotACommit(String),
+    #[error("not a tree object: {0}")]
+    NotATree(String),
+    #[error("invalid date format: {0}")]
+    InvalidDate(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+    pub seconds: i64,
+    pub offset_minutes: i32,
+}
+
+impl Timestamp {
+    pub fn now() -> anyhow::Result<Timestamp> {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        Ok(Timestamp { seconds, offset_minutes: local_offset_minutes(seconds) })
+    }
+
+    pub fn from_env(var: &str) -> anyhow::Result<Timestamp> {
+        match env::var(var) {
+            Ok(value) => Ok(parse_date(&value).ok_or(Error::InvalidDate(value))?),
+            Err(_) => Timestamp::now(),
+        }
+    }
+
+    pub fn author() -> anyhow::Result<Timestamp> {
+        Timestamp::from_env("GIT_AUTHOR_DATE")
+    }
+
+    pub fn committer() -> anyhow::Result<Timestamp> {
+        Timestamp::from_env("GIT_COMMITTER_DATE")
+    }
+}
+
+impl std::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let sign = if self.offset_minutes < 0 { '-' } else { '+' };
+        let offset = self.offset_minutes.abs();
+        write!(f, "{} {sign}{:02}{:02}", self.seconds, offset / 60, offset % 60)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Commit {
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub author: String,
+    pub committer: String,
+    pub message: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Identity {
+    pub name: String,
+    pub email: String,
+}
+
+impl Identity {
+    fn from_env(name_var: &str, email_var: &str) -> Identity {
+        let user = env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+        Identity {
+            name: env::var(name_var).unwrap_or_else(|_| user.clone()),
+            email: env::var(email_var).unwrap_or_else(|_| format!("{user}@localhost")),
+        }
+    }
+
+    pub fn author() -> Identity {
+        Identity::from_env("GIT_AUTHOR_NAME", "GIT_AUTHOR_EMAIL")
+    }
+
+    pub fn committer() -> Identity {
+        Identity::from_env("GIT_COMMITTER_NAME", "GIT_COMMITTER_EMAIL")
+    }
+
+    pub fn signature(&self, when: Timestamp) -> String {
+        format!("{} <{}> {when}", self.name, self.email)
+    }
+}
+
+fn commit_content<S: AsRef<str>>(tree: &str, parents: &[S], author: &str, committer: &str, message: &str) -> String {
+    let mut content = format!("tree {tree}\n");
+    for parent in parents {
+        content.push_str(&format!("parent {}\n", parent.as_ref()));
+    }
+    content.push_str(&format!("author {author}\ncommitter {committer}\n\n{message}"));
+    if !message.ends_with('\n') {
+        content.push('\n');
+    }
+    content
+}
+
+fn parse_commit_content(content: &[u8]) -> Result<Commit, Error> {
+    let content = String::from_utf8_lossy(content);
+    let (headers, message) = content.split_once("\n\n").ok_or(Error::MalformedCommit)?;
+    let mut tree = None;
+    let mut parents = Vec::new();
+    let mut author = None;
+    let mut committer = None;
+    for line in headers.lines() {
+        if line.starts_with(' ') {
+            continue;
+        }
+        match line.split_once(' ') {
+            Some(("tree", value)) => tree = Some(value.to_string()),
+            Some(("parent", value)) => parents.push(value.to_string()),
+            Some(("author", value)) => author = Some(value.to_string()),
+            Some(("committer", value)) => committer = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Ok(Commit {
+        tree: tree.ok_or(Error::MalformedCommit)?,
+        parents,
+        author: author.ok_or(Error::MalformedCommit)?,
+        committer: committer.ok_or(Error::MalformedCommit)?,
+        message: message.to_string(),
+    })
+}
+
+pub fn parse_commit(hash: &str) -> anyhow::Result<Commit> {
+    let (object_type, content) = catfile::read_object(hash, ParseMode::Strict)?;
+    if object_type != ObjectType::Commit {
+        return Err(Error::NotACommit(hash.to_string()).into());
+    }
+    Ok(parse_commit_content(&content)?)
+}
+
+/// Parses a commit for history walks, treating the boundary commits in
+/// `shallow` (see [`repo::shallow_commits`]) as parentless.
+pub fn parse_commit_with(hash: &str, shallow: &HashSet<String>) -> anyhow::Result<Commit> {
+    let mut commit = parse_commit(hash)?;
+    if shallow.contains(&hash.to_ascii_lowercase()) {
+        commit.parents.clear();
+    }
+    Ok(commit)
+}
+
+pub fn split_signature(signature: &str) -> Option<(&str, i64, &str)> {
+    let mut parts = signature.rsplitn(3, ' ');
+    let offset = parts.next()?;
+    let timestamp = parts.next()?.parse().ok()?;
+    let identity = parts.next()?;
+    Some((identity, timestamp, offset))
+}
+
+fn civil_from_days(days: i64) -> (i64, usize, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 2 } else { month_index - 10 };
+    let year = year_of_era + era * 400 + if month < 2 { 1 } else { 0 };
+    (year, month as usize, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(unix)]
+fn local_offset_minutes(seconds: i64) -> i32 {
+    let time = seconds as libc::time_t;
+    // SAFETY: localtime_r only writes into the tm we own.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    (tm.tm_gmtoff / 60) as i32
+}
+
+#[cfg(not(unix))]
+fn local_offset_minutes(_seconds: i64) -> i32 {
+    0
+}
+
+fn parse_offset(offset: &str) -> Option<i32> {
+    if offset == "Z" {
+        return Some(0);
+    }
+    let (sign, digits) = offset.split_at_checked(1)?;
+    let digits = digits.replace(':', "");
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let minutes = digits[..2].parse::<i32>().ok()? * 60 + digits[2..].parse::<i32>().ok()?;
+    match sign {
+        "+" => Some(minutes),
+        "-" => Some(-minutes),
+        _ => None,
+    }
+}
+
+fn parse_clock(clock: &str) -> Option<i64> {
+    let mut parts = clock.split(':').map(|part| part.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next().unwrap_or(Some(0))?);
+    (parts.next().is_none() && hours < 24 && minutes < 60 && seconds < 61).then_some(hours * 3600 + minutes * 60 + seconds)
+}
+
+fn local_timestamp(days: i64, clock: i64, offset: Option<i32>) -> Timestamp {
+    let naive = days * 86400 + clock;
+    let offset_minutes = offset.unwrap_or_else(|| local_offset_minutes(naive));
+    Timestamp { seconds: naive - offset_minutes as i64 * 60, offset_minutes }
+}
+
+fn parse_iso_date(value: &str) -> Option<Timestamp> {
+    let (date, rest) = value.split_at_checked(10)?;
+    let mut fields = date.split('-').map(|field| field.parse::<i64>().ok());
+    let (year, month, day) = (fields.next()??, fields.next()??, fields.next()??);
+    let rest = rest.strip_prefix(['T', ' '])?;
+    let split = rest.find(['+', '-', 'Z', ' ']).unwrap_or(rest.len());
+    let (clock, offset) = rest.split_at(split);
+    let offset = match offset.trim() {
+        "" => None,
+        offset => Some(parse_offset(offset)?),
+    };
+    Some(local_timestamp(days_from_civil(year, month, day), parse_clock(clock)?, offset))
+}
+
+fn parse_rfc2822_date(value: &str) -> Option<Timestamp> {
+    let value = value.split_once(", ").map_or(value, |(_, rest)| rest);
+    let fields: Vec<&str> = value.split_whitespace().collect();
+    let [day, month, year, clock, rest @ ..] = fields.as_slice() else {
+        return None;
+    };
+    let month = MONTHS.iter().position(|name| name.eq_ignore_ascii_case(month))? as i64 + 1;
+    let offset = match rest {
+        [] => None,
+        [offset] => Some(parse_offset(offset)?),
+        _ => return None,
+    };
+    Some(local_timestamp(days_from_civil(year.parse().ok()?, month, day.parse().ok()?), parse_clock(clock)?, offset))
+}
+
+pub fn parse_date(value: &str) -> Option<Timestamp> {
+    let value = value.trim();
+    let raw = value.strip_prefix('@').unwrap_or(value);
+    let (seconds, offset) = raw.split_once(' ').unwrap_or((raw, "+0000"));
+    if let Ok(seconds) = seconds.parse::<i64>() {
+        return Some(Timestamp { seconds, offset_minutes: parse_offset(offset)? });
+    }
+    parse_iso_date(value).or_else(|| parse_rfc2822_date(value))
+}
+
+pub fn format_date(timestamp: i64, offset: &str) -> Option<String> {
+    let (sign, digits) = offset.split_at_checked(1)?;
+    let hours: i64 = digits.get(..2)?.parse().ok()?;
+    let minutes: i64 = digits.get(2..)?.parse().ok()?;
+    let offset_seconds = match sign {
+        "+" => hours * 3600 + minutes * 60,
+        "-" => -(hours * 3600 + minutes * 60),
+        _ => return None,
+    };
+    let local = timestamp + offset_seconds;
+    let days = local.div_euclid(86400);
+    let seconds = local.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    Some(format!(
+        "{} {} {day} {:02}:{:02}:{:02} {year} {offset}",
+        WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+        MONTHS[month],
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+    ))
+}
+
+/// Breadth-first walk from `starts` that records, but does not go past,
+/// commits matching `stop`.
+fn walk<F, S>(starts: Vec<String>, parents: &F, stop: S) -> anyhow::Result<Vec<String>>
+where
+    F: Fn(&str) -> anyhow::Result<Vec<String>>,
+    S: Fn(&str) -> bool,
+{
+    let mut seen: HashSet<String> = starts.iter().cloned().collect();
+    let mut order = Vec::new();
+    let mut queue = VecDeque::from(starts);
+    while let Some(hash) = queue.pop_front() {
+        if !stop(&hash) {
+            for parent in parents(&hash)? {
+                if seen.insert(parent.clone()) {
+                    queue.push_back(parent);
+                }
+            }
+        }
+        order.push(hash);
+    }
+    Ok(order)
+}
+
+fn merge_base_with<F>(a: &str, b: &str, parents: &F) -> anyhow::Result<Option<String>>
+where
+    F: Fn(&str) -> anyhow::Result<Vec<String>>,
+{
+    let reachable_from_a: HashSet<String> = walk(vec![a.to_string()], parents, |_| false)?.into_iter().collect();
+    let candidates: Vec<String> = walk(vec![b.to_string()], parents, |hash| reachable_from_a.contains(hash))?
+        .into_iter()
+        .filter(|hash| reachable_from_a.contains(hash))
+        .collect();
+    let mut below = Vec::new();
+    for candidate in &candidates {
+        below.extend(parents(candidate)?);
+    }
+    below.sort();
+    below.dedup();
+    let redundant: HashSet<String> = walk(below, parents, |_| false)?.into_iter().collect();
+    Ok(candidates.into_iter().find(|candidate| !redundant.contains(candidate)))
+}
+
+pub fn merge_base(a: &str, b: &str) -> anyhow::Result<Option<String>> {
+    let shallow = repo::shallow_commits()?;
+    let parents = |hash: &str| Ok(parse_commit_with(hash, &shallow)?.parents);
+    merge_base_with(&refs::rev_parse(a)?, &refs::rev_parse(b)?, &parents)
+}
+
+fn rev_list_with<F>(start: &str, max_count: Option<usize>, read: &F) -> anyhow::Result<Vec<String>>
+where
+    F: Fn(&str) -> anyhow::Result<(i64, Vec<String>)>,
+{
+    let mut seen = HashSet::from([start.to_string()]);
+    let mut queued = 0usize;
+    let mut queue = BinaryHeap::new();
+    let (time, parents) = read(start)?;
+    queue.push((time, Reverse(queued), start.to_string(), parents));
+    let mut order = Vec::new();
+    while let Some((_, _, hash, parents)) = queue.pop() {
+        if max_count.is_some_and(|max| order.len() >= max) {
+            break;
+        }
+        for parent in parents {
+            if seen.insert(parent.clone()) {
+                let (time, grandparents) = read(&parent)?;
+                queued += 1;
+                queue.push((time, Reverse(queued), parent, grandparents));
+            }
+        }
+        order.push(hash);
+    }
+    Ok(order)
+}
+
+pub fn rev_list(rev: &str, max_count: Option<usize>, count: bool) -> anyhow::Result<()> {
+    let shallow = repo::shallow_commits()?;
+    let read = |hash: &str| {
+        let commit = parse_commit_with(hash, &shallow)?;
+        let time = split_signature(&commit.committer).map_or(0, |(_, time, _)| time);
+        Ok((time, commit.parents))
+    };
+    let hashes = rev_list_with(&tag::peel_tag(&refs::rev_parse(rev)?)?, max_count, &read)?;
+    if count {
+        println!("{}", hashes.len());
+        return Ok(());
+    }
+    for hash in hashes {
+        println!("{hash}");
+    }
+    Ok(())
+}
+
+fn signatures() -> anyhow::Result<(String, String)> {
+    Ok((
+        Identity::author().signature(Timestamp::author()?),
+        Identity::committer().signature(Timestamp::committer()?),
+    ))
+}
+
+fn resolve_tree(name: &str) -> anyhow::Result<String> {
+    let hash = tag::peel_tag(&refs::rev_parse(name)?)?;
+    match catfile::object_type(&hash)? {
+        ObjectType::Tree => Ok(hash),
+        ObjectType::Commit => Ok(parse_commit(&hash)?.tree),
+        _ => Err(Error::NotATree(name.to_string()).into()),
+    }
+}
+
+fn resolve_commit(name: &str) -> anyhow::Result<String> {
+    let hash = tag::peel_tag(&refs::rev_parse(name)?)?;
+    match catfile::object_type(&hash)? {
+        ObjectType::Commit => Ok(hash),
+        _ => Err(Error::NotACommit(name.to_string()).into()),
+    }
+}
+
+pub fn commit_tree(tree: &str, parents: &[String], message: &str) -> anyhow::Result<String> {
+    let tree = resolve_tree(tree)?;
+    let parents = parents.iter().map(|parent| resolve_commit(parent)).collect::<anyhow::Result<Vec<_>>>()?;
+    let (author, committer) = signatures()?;
+    let content = commit_content(&tree, &parents, &author, &committer, message);
+    let hash = catfile::write_object_content(ObjectType::Commit, content.as_bytes())?;
+    let subject = message.lines().next().unwrap_or("");
+    let action = match parents.len() {
+        0 => "commit (initial)",
+        1 => "commit",
+        _ => "commit (merge)",
+    };
+    refs::update_head(&hash, &format!("{action}: {subject}"))?;
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn jane() -> String {
+        Identity { name: "Jane Doe".to_string(), email: "jane@example.com".to_string() }.signature(Timestamp { seconds: 1700000000, offset_minutes: 0 })
+    }
+
+    #[test]
+    fn test_signature() {
+        let expected_signature = "Jane Doe <jane@example.com> 1700000000 +0000";
+
+        assert_eq!(jane(), expected_signature);
+    }
+
+    #[test]
+    fn commit_content_without_parent_hashes_like_git() {
+        let tree = "0df530b3a655a943b379c67747b940b7199f6116";
+        let expected_hash = "ab3e94d6b7ec38af9d3dee5b424a13a292742c4c";
+
+        let content = commit_content::<&str>(tree, &[], &jane(), &jane(), "initial commit");
+        let payload = catfile::object_payload(ObjectType::Commit, content.as_bytes());
+        assert_eq!(catfile::ObjectFormat::Sha1.hash(&payload), expected_hash);
+    }
+
+    #[test]
+    fn commit_content_with_parent_hashes_like_git() {
+        let tree = "0df530b3a655a943b379c67747b940b7199f6116";
+        let parent = "ab3e94d6b7ec38af9d3dee5b424a13a292742c4c";
+        let expected_hash = "6255d532c5299a066ba45a9aa394aebb96049e06";
+
+        let content = commit_content(tree, &[parent], &jane(), &jane(), "second commit");
+        let payload = catfile::object_payload(ObjectType::Commit, content.as_bytes());
+        assert_eq!(catfile::ObjectFormat::Sha1.hash(&payload), expected_hash);
+    }
+
+    #[test]
+    fn commit_content_with_two_parents_hashes_like_git() {
+        let tree = "aaff74984cccd156a469afa7d9ab10e4777beb24";
+        let parents = ["8213f44b3468799ae47e3e57b7f155eb7966a3c7", "f6aac7e14371c4d5c4de019180cc253669a950b5"];
+        let expected_hash = "dfef25da66170c32ea6ce9c37c52f9bc8ec8ca0e";
+
+        let content = commit_content(tree, &parents, &jane(), &jane(), "merge commit");
+        let payload = catfile::object_payload(ObjectType::Commit, content.as_bytes());
+        assert_eq!(catfile::ObjectFormat::Sha1.hash(&payload), expected_hash);
+        assert_eq!(parse_commit_content(content.as_bytes()).map(|commit| commit.parents), Ok(parents.map(str::to_string).to_vec()));
+    }
+
+    #[test]
+    fn parse_commit_content_reads_headers_and_message() -> Result<(), Error> {
+        let content = commit_content("t", &["p1"], &jane(), &jane(), "second commit");
+        let expected_commit = Commit {
+            tree: "t".to_string(),
+            parents: vec!["p1".to_string()],
+            author: jane(),
+            committer: jane(),
+            message: "second commit\n".to_string(),
+        };
+
+        let commit = parse_commit_content(content.as_bytes())?;
+        assert_eq!(commit, expected_commit);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_commit_content_with_root_commit_has_no_parents() -> Result<(), Error> {
+        let content = commit_content::<&str>("t", &[], &jane(), &jane(), "initial commit");
+
+        let commit = parse_commit_content(content.as_bytes())?;
+        assert!(commit.parents.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_commit_content_skips_gpgsig_continuation_lines() -> Result<(), Error> {
+        let content = format!(
+            "tree t\nparent p1\nauthor {0}\ncommitter {0}\ngpgsig -----BEGIN PGP SIGNATURE-----\n \n \
+             iQEzBAABCAAdFiEE\n parent not-a-parent\n =abcd\n -----END PGP SIGNATURE-----\n\nsigned commit\n",
+            jane(),
+        );
+        let expected_commit = Commit {
+            tree: "t".to_string(),
+            parents: vec!["p1".to_string()],
+            author: jane(),
+            committer: jane(),
+            message: "signed commit\n".to_string(),
+        };
+
+        let commit = parse_commit_content(content.as_bytes())?;
+        assert_eq!(commit, expected_commit);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_commit_content_is_error_without_tree() {
+        let content = format!("author {}\ncommitter {}\n\nmessage\n", jane(), jane());
+        let expected_error = Err(Error::MalformedCommit);
+
+        let actual_result = parse_commit_content(content.as_bytes());
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn test_split_signature() {
+        let expected_parts = Some(("Jane Doe <jane@example.com>", 1700000000, "+0000"));
+
+        assert_eq!(split_signature(&jane()), expected_parts);
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(1700000000, "+0000").as_deref(), Some("Tue Nov 14 22:13:20 2023 +0000"));
+        assert_eq!(format_date(1700000000, "-0800").as_deref(), Some("Tue Nov 14 14:13:20 2023 -0800"));
+        assert_eq!(format_date(1700000000, "+0530").as_deref(), Some("Wed Nov 15 03:43:20 2023 +0530"));
+        assert_eq!(format_date(951782400, "+0000").as_deref(), Some("Tue Feb 29 00:00:00 2000 +0000"));
+    }
+
+    #[test]
+    fn commit_content_does_not_double_trailing_newline() {
+        let content = commit_content::<&str>("t", &[], "a", "c", "message\n");
+        let expected_content = "tree t\nauthor a\ncommitter c\n\nmessage\n";
+
+        assert_eq!(content, expected_content);
+    }
+
+    fn sample_graph(hash: &str) -> anyhow::Result<Vec<String>> {
+        // a - b - c - d (main)
+        //      \       \
+        //       e - f - g (topic, merges d)
+        let parents: &[&str] = match hash {
+            "b" => &["a"],
+            "c" => &["b"],
+            "d" => &["c"],
+            "e" => &["b"],
+            "f" => &["e"],
+            "g" => &["f", "d"],
+            _ => &[],
+        };
+        Ok(parents.iter().map(|parent| parent.to_string()).collect())
+    }
+
+    #[test]
+    fn merge_base_finds_fork_point() -> anyhow::Result<()> {
+        assert_eq!(merge_base_with("c", "f", &sample_graph)?.as_deref(), Some("b"));
+        Ok(())
+    }
+
+    #[test]
+    fn merge_base_with_ancestor_is_the_ancestor() -> anyhow::Result<()> {
+        assert_eq!(merge_base_with("b", "d", &sample_graph)?.as_deref(), Some("b"));
+        assert_eq!(merge_base_with("d", "d", &sample_graph)?.as_deref(), Some("d"));
+        Ok(())
+    }
+
+    #[test]
+    fn merge_base_skips_ancestors_of_better_common_commits() -> anyhow::Result<()> {
+        assert_eq!(merge_base_with("g", "d", &sample_graph)?.as_deref(), Some("d"));
+        assert_eq!(merge_base_with("g", "c", &sample_graph)?.as_deref(), Some("c"));
+        Ok(())
+    }
+
+    #[test]
+    fn merge_base_reads_each_commit_a_bounded_number_of_times() -> anyhow::Result<()> {
+        let reads = std::cell::Cell::new(0);
+        let linear = |hash: &str| {
+            reads.set(reads.get() + 1);
+            let n: usize = hash.parse()?;
+            Ok(if n == 0 { Vec::new() } else { vec![(n - 1).to_string()] })
+        };
+
+        assert_eq!(merge_base_with("9999", "5000", &linear)?.as_deref(), Some("5000"));
+        assert!(reads.get() <= 3 * 10_000);
+        Ok(())
+    }
+
+    #[test]
+    fn merge_base_is_none_for_unrelated_histories() -> anyhow::Result<()> {
+        assert_eq!(merge_base_with("a", "z", &sample_graph)?, None);
+        Ok(())
+    }
+
+    fn dated_graph(hash: &str) -> anyhow::Result<(i64, Vec<String>)> {
+        let time = match hash {
+            "a" => 1,
+            "b" => 2,
+            "e" => 3,
+            "c" => 4,
+            "f" => 5,
+            "d" => 6,
+            _ => 7,
+        };
+        Ok((time, sample_graph(hash)?))
+    }
+
+    #[test]
+    fn rev_list_orders_by_date_and_visits_merged_commits_once() -> anyhow::Result<()> {
+        assert_eq!(rev_list_with("g", None, &dated_graph)?, ["g", "d", "f", "c", "e", "b", "a"]);
+        assert_eq!(rev_list_with("d", None, &dated_graph)?, ["d", "c", "b", "a"]);
+        Ok(())
+    }
+
+    #[test]
+    fn rev_list_stops_at_max_count() -> anyhow::Result<()> {
+        assert_eq!(rev_list_with("g", Some(3), &dated_graph)?, ["g", "d", "f"]);
+        assert!(rev_list_with("g", Some(0), &dated_graph)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_timestamp_display() {
+        assert_eq!(Timestamp { seconds: 1112911993, offset_minutes: 120 }.to_string(), "1112911993 +0200");
+        assert_eq!(Timestamp { seconds: 1112911993, offset_minutes: -270 }.to_string(), "1112911993 -0430");
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let at = |seconds, offset_minutes| Some(Timestamp { seconds, offset_minutes });
+
+        assert_eq!(parse_date("1112911993 +0200"), at(1112911993, 120));
+        assert_eq!(parse_date("@1112911993 -0700"), at(1112911993, -420));
+        assert_eq!(parse_date("2005-04-07T22:13:13+02:00"), at(1112904793, 120));
+        assert_eq!(parse_date("2005-04-07 22:13:13 -0430"), at(1112928193, -270));
+        assert_eq!(parse_date("2005-04-07T22:13:13Z"), at(1112911993, 0));
+        assert_eq!(parse_date("Thu, 07 Apr 2005 22:13:13 +0200"), at(1112904793, 120));
+        assert_eq!(parse_date("yesterday"), None);
+    }
+
+    #[test]
+    fn signatures_with_pinned_dates_hash_like_git() -> anyhow::Result<()> {
+        let _lock = crate::repo::ENV_LOCK.lock().unwrap();
+        let vars = [
+            ("GIT_AUTHOR_NAME", "Jane Doe"),
+            ("GIT_AUTHOR_EMAIL", "jane@example.com"),
+            ("GIT_AUTHOR_DATE", "2005-04-07 22:13:13 -0430"),
+            ("GIT_COMMITTER_NAME", "Jane Doe"),
+            ("GIT_COMMITTER_EMAIL", "jane@example.com"),
+            ("GIT_COMMITTER_DATE", "2005-04-07 22:13:13 -0430"),
+        ];
+        for (name, value) in vars {
+            env::set_var(name, value);
+        }
+        let result = signatures();
+        for (name, _) in vars {
+            env::remove_var(name);
+        }
+        let expected_hash = "0466044afd0b46181d8749c0cd786ed7ae55a6c7";
+
+        let (author, committer) = result?;
+        let content = commit_content::<&str>("4b825dc642cb6eb9a060e54bf8d69288fbee4904", &[], &author, &committer, "pinned");
+        let payload = catfile::object_payload(ObjectType::Commit, content.as_bytes());
+        assert_eq!(catfile::ObjectFormat::Sha1.hash(&payload), expected_hash);
+        Ok(())
+    }
+
+    #[test]
+    fn timestamp_from_env_is_error_with_invalid_date() {
+        let _lock = crate::repo::ENV_LOCK.lock().unwrap();
+        env::set_var("GIT_AUTHOR_DATE", "not a date");
+        let result = Timestamp::author();
+        env::remove_var("GIT_AUTHOR_DATE");
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn commit_tree_resolves_and_type_checks_arguments() -> anyhow::Result<()> {
+        let _lock = crate::repo::ENV_LOCK.lock().unwrap();
+        let git_dir = std::env::temp_dir().join("commit_tree_type_checks").join(".git");
+        let _ = std::fs::remove_dir_all(&git_dir);
+        std::fs::create_dir_all(git_dir.join("objects"))?;
+        std::fs::create_dir_all(git_dir.join("refs/heads"))?;
+        std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n")?;
+        env::set_var("GIT_DIR", &git_dir);
+
+        let result = (|| {
+            let blob = catfile::write_object_content(ObjectType::Blob, b"not a tree")?;
+            let tree = catfile::write_object_content(ObjectType::Tree, b"")?;
+            let not_a_tree = commit_tree(&blob, &[], "bad").unwrap_err().downcast::<Error>()?;
+            let not_a_commit = commit_tree(&tree, std::slice::from_ref(&tree), "bad").unwrap_err().downcast::<Error>()?;
+            let head_after_errors = refs::resolve_ref("HEAD").is_ok();
+            let root = commit_tree(&tree[..7], &[], "root")?;
+            let child = commit_tree(&format!("{root}^{{tree}}"), &["main".to_string()], "child")?;
+            Ok::<_, anyhow::Error>((blob, tree, not_a_tree, not_a_commit, head_after_errors, root, parse_commit(&child)?))
+        })();
+        env::remove_var("GIT_DIR");
+
+        let (blob, tree, not_a_tree, not_a_commit, head_after_errors, root, child) = result?;
+        assert_eq!(not_a_tree, Error::NotATree(blob));
+        assert_eq!(not_a_commit, Error::NotACommit(tree.clone()));
+        assert!(!head_after_errors);
+        assert_eq!(child.tree, tree);
+        assert_eq!(child.parents, [root]);
+        Ok(())
+    }
+
+    #[test]
+    fn shallow_boundary_commits_have_no_parents() -> anyhow::Result<()> {
+        let _lock = crate::repo::ENV_LOCK.lock().unwrap();
+        let git_dir = std::env::temp_dir().join("shallow_boundary_commits").join(".git");
+        let _ = std::fs::remove_dir_all(&git_dir);
+        std::fs::create_dir_all(git_dir.join("objects"))?;
+        env::set_var("GIT_DIR", &git_dir);
+
+        let result = (|| {
+            let missing_parent = "ab3e94d6b7ec38af9d3dee5b424a13a292742c4c";
+            let content = commit_content("4b825dc642cb6eb9a060e54bf8d69288fbee4904", &[missing_parent], &jane(), &jane(), "boundary");
+            let boundary = catfile::write_object_content(ObjectType::Commit, content.as_bytes())?;
+            std::fs::write(git_dir.join("shallow"), format!("{boundary}\n"))?;
+            let shallow = repo::shallow_commits()?;
+            Ok::<_, anyhow::Error>((parse_commit(&boundary)?.parents, parse_commit_with(&boundary, &shallow)?.parents, catfile::object_exists(missing_parent)))
+        })();
+        env::remove_var("GIT_DIR");
+
+        let (recorded, shallow, parent_exists) = result?;
+        assert_eq!(recorded, ["ab3e94d6b7ec38af9d3dee5b424a13a292742c4c"]);
+        assert!(shallow.is_empty());
+        assert!(!parent_exists);
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use crate::repo;
+
+fn section_name(header: &str) -> String {
+    let header = header.trim();
+    match header.split_once(char::is_whitespace) {
+        Some((section, subsection)) => {
+            let subsection = subsection.trim().trim_matches('"');
+            format!("{}.{subsection}", section.to_ascii_lowercase())
+        }
+        None => header.to_ascii_lowercase(),
+    }
+}
+
+pub fn parse(content: &str) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    let mut section = String::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            section = section_name(header);
+            continue;
+        }
+        let (key, value) = line.split_once('=').unwrap_or((line, "true"));
+        values.insert(format!("{section}.{}", key.trim().to_ascii_lowercase()), value.trim().to_string());
+    }
+    values
+}
+
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" | "" => Some(false),
+        _ => None,
+    }
+}
+
+pub(crate) fn get_in(git_dir: &Path, key: &str) -> Option<String> {
+    let content = fs::read_to_string(git_dir.join("config")).ok()?;
+    parse(&content).remove(&key.to_ascii_lowercase())
+}
+
+pub fn get(key: &str) -> Option<String> {
+    get_in(&repo::git_dir().ok()?, key)
+}
+
+pub fn get_bool(key: &str) -> Option<bool> {
+    parse_bool(&get(key)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_reads_sections_and_keys() {
+        let content = "[core]\n\trepositoryformatversion = 0\n  bare=false  \n[Remote \"origin\"]\n\turl = https://example.com/repo.git\n";
+        let values = parse(content);
+
+        assert_eq!(values.get("core.repositoryformatversion").map(String::as_str), Some("0"));
+        assert_eq!(values.get("core.bare").map(String::as_str), Some("false"));
+        assert_eq!(values.get("remote.origin.url").map(String::as_str), Some("https://example.com/repo.git"));
+    }
+
+    #[test]
+    fn parse_lowercases_keys_and_skips_comments() {
+        let content = "# comment\n[Core]\n; another\n\tFileMode = true\n\tignorecase\n";
+        let values = parse(content);
+
+        assert_eq!(values.get("core.filemode").map(String::as_str), Some("true"));
+        assert_eq!(values.get("core.ignorecase").map(String::as_str), Some("true"));
+        assert_eq!(values.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_bool() {
+        assert_eq!(parse_bool("TRUE"), Some(true));
+        assert_eq!(parse_bool("off"), Some(false));
+        assert_eq!(parse_bool("maybe"), None);
+    }
+}
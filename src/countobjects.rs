@@ -0,0 +1,94 @@
+use std::fs;
+use std::path::Path;
+use crate::catfile::ObjectFormat;
+use crate::fsck;
+use crate::pack;
+use crate::repo;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ObjectCounts {
+    count: u64,
+    size: u64,
+    in_pack: u64,
+    packs: u64,
+    size_pack: u64,
+}
+
+#[cfg(unix)]
+fn disk_usage(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn disk_usage(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+fn count_objects_in(objects_dir: &Path, format: ObjectFormat) -> anyhow::Result<ObjectCounts> {
+    let mut counts = ObjectCounts::default();
+    for (_, path) in fsck::loose_objects(objects_dir, format)? {
+        counts.count += 1;
+        counts.size += disk_usage(&fs::metadata(path)?);
+    }
+    for index_path in pack::pack_indexes(objects_dir) {
+        let pack_path = index_path.with_extension("pack");
+        if !pack_path.is_file() {
+            continue;
+        }
+        counts.packs += 1;
+        counts.in_pack += pack::pack_object_count(&fs::read(&index_path)?)? as u64;
+        counts.size_pack += fs::metadata(&index_path)?.len() + fs::metadata(&pack_path)?.len();
+    }
+    Ok(counts)
+}
+
+fn format_counts(counts: &ObjectCounts, verbose: bool) -> String {
+    if !verbose {
+        return format!("{} objects, {} kilobytes\n", counts.count, counts.size / 1024);
+    }
+    format!(
+        "count: {}\nsize: {}\nin-pack: {}\npacks: {}\nsize-pack: {}\n",
+        counts.count,
+        counts.size / 1024,
+        counts.in_pack,
+        counts.packs,
+        counts.size_pack / 1024,
+    )
+}
+
+pub fn count_objects(verbose: bool) -> anyhow::Result<()> {
+    let git_dir = repo::git_dir()?;
+    let counts = count_objects_in(&git_dir.join("objects"), ObjectFormat::of(&git_dir))?;
+    print!("{}", format_counts(&counts, verbose));
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_counts_reports_kilobytes() {
+        let counts = ObjectCounts { count: 3, size: 12288, in_pack: 9, packs: 1, size_pack: 2560 };
+
+        assert_eq!(format_counts(&counts, false), "3 objects, 12 kilobytes\n");
+        assert_eq!(format_counts(&counts, true), "count: 3\nsize: 12\nin-pack: 9\npacks: 1\nsize-pack: 2\n");
+    }
+
+    #[test]
+    fn count_objects_in_counts_loose_objects_and_packs() -> anyhow::Result<()> {
+        let objects_dir = std::env::temp_dir().join("count_objects_dir");
+        let _ = fs::remove_dir_all(&objects_dir);
+        let loose = objects_dir.join("bd").join("9dbf5aae1a3862dd1526723246b20206e5fc37");
+        fs::create_dir_all(loose.parent().unwrap())?;
+        fs::write(&loose, b"loose")?;
+        let (data, objects) = pack::write_pack(&[(crate::catfile::ObjectType::Blob, b"packed".to_vec())], ObjectFormat::Sha1)?;
+        fs::create_dir_all(objects_dir.join("pack"))?;
+        pack::write_pack_files(&objects_dir.join("pack").join("pack"), &data, &objects, ObjectFormat::Sha1)?;
+
+        let counts = count_objects_in(&objects_dir, ObjectFormat::Sha1)?;
+        assert_eq!((counts.count, counts.in_pack, counts.packs), (1, 1, 1));
+        Ok(())
+    }
+}
@@ -0,0 +1,417 @@
+use std::cmp::Ordering;
+use crate::catfile;
+use crate::status::ChangeKind;
+use crate::tree;
+use crate::tree::TreeEntry;
+
+const CONTEXT: usize = 3;
+const BINARY_CHECK_LEN: usize = 8000;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct TreeChange {
+    pub path: String,
+    pub old_mode: Option<String>,
+    pub new_mode: Option<String>,
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+    pub status: ChangeKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+fn split_lines(content: &[u8]) -> Vec<&[u8]> {
+    content.split_inclusive(|&byte| byte == b'\n').collect()
+}
+
+fn furthest_reaching<F>(v: &mut [isize], offset: isize, d: isize, k: isize, (n, m): (isize, isize), same: F) -> (isize, isize)
+where
+    F: Fn(isize, isize) -> bool,
+{
+    let index = |k: isize| (k + offset) as usize;
+    let start = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+        v[index(k + 1)]
+    } else {
+        v[index(k - 1)] + 1
+    };
+    let mut x = start;
+    while x < n && x - k < m && same(x, x - k) {
+        x += 1;
+    }
+    v[index(k)] = x;
+    (start, x)
+}
+
+/// Finds the snake in the middle of a shortest edit script (Myers' linear
+/// space refinement), returning its start and end points.
+fn middle_snake(a: &[&[u8]], b: &[&[u8]]) -> ((usize, usize), (usize, usize)) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    let index = |k: isize| (k + offset) as usize;
+    let mut forward = vec![0; 2 * offset as usize + 1];
+    let mut backward = vec![0; 2 * offset as usize + 1];
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let (start, x) = furthest_reaching(&mut forward, offset, d, k, (n, m), |x, y| a[x as usize] == b[y as usize]);
+            let reverse_k = delta - k;
+            if delta % 2 != 0 && (1 - d..d).contains(&reverse_k) && x >= n - backward[index(reverse_k)] {
+                return ((start as usize, (start - k) as usize), (x as usize, (x - k) as usize));
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let (start, x) = furthest_reaching(&mut backward, offset, d, k, (n, m), |x, y| a[(n - 1 - x) as usize] == b[(m - 1 - y) as usize]);
+            let forward_k = delta - k;
+            if delta % 2 == 0 && (-d..=d).contains(&forward_k) && forward[index(forward_k)] >= n - x {
+                return (((n - x) as usize, (m - x + k) as usize), ((n - start) as usize, (m - start + k) as usize));
+            }
+        }
+    }
+    unreachable!("edit paths always meet within (n + m) / 2 steps")
+}
+
+fn diff_range(a: &[&[u8]], b: &[&[u8]], (a_start, b_start): (usize, usize), edits: &mut Vec<Edit>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    edits.extend((0..prefix).map(|i| Edit::Equal(a_start + i, b_start + i)));
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let (a_start, b_start) = (a_start + prefix, b_start + prefix);
+    let suffix = a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+
+    if a.is_empty() || b.is_empty() {
+        edits.extend((0..a.len()).map(|i| Edit::Delete(a_start + i)));
+        edits.extend((0..b.len()).map(|j| Edit::Insert(b_start + j)));
+    } else {
+        let ((x, y), (u, v)) = middle_snake(a, b);
+        diff_range(&a[..x], &b[..y], (a_start, b_start), edits);
+        edits.extend((0..u - x).map(|i| Edit::Equal(a_start + x + i, b_start + y + i)));
+        diff_range(&a[u..], &b[v..], (a_start + u, b_start + v), edits);
+    }
+    edits.extend((0..suffix).map(|i| Edit::Equal(a_start + a.len() + i, b_start + b.len() + i)));
+}
+
+fn diff_lines(a: &[&[u8]], b: &[&[u8]]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    diff_range(a, b, (0, 0), &mut edits);
+    // Within each changed block, show removed lines before added ones.
+    for block in edits.split_mut(|edit| matches!(edit, Edit::Equal(..))) {
+        block.sort_by_key(|edit| matches!(edit, Edit::Insert(_)));
+    }
+    edits
+}
+
+fn hunk_ranges(edits: &[Edit]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (position, _) in edits.iter().enumerate().filter(|(_, edit)| !matches!(edit, Edit::Equal(..))) {
+        let start = position.saturating_sub(CONTEXT);
+        let end = (position + 1 + CONTEXT).min(edits.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+}
+
+fn format_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        count => format!("{},{count}", start + 1),
+    }
+}
+
+fn push_line(out: &mut String, prefix: char, line: &[u8]) {
+    out.push(prefix);
+    out.push_str(&String::from_utf8_lossy(line));
+    if !line.ends_with(b"\n") {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+pub fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(BINARY_CHECK_LEN).any(|&byte| byte == 0)
+}
+
+pub fn unified_diff(a: &[u8], b: &[u8]) -> String {
+    if a == b {
+        return String::new();
+    }
+    if is_binary(a) || is_binary(b) {
+        return "Binary files differ\n".to_string();
+    }
+    let (a_lines, b_lines) = (split_lines(a), split_lines(b));
+    let edits = diff_lines(&a_lines, &b_lines);
+
+    let mut out = String::new();
+    for (start, end) in hunk_ranges(&edits) {
+        let hunk = &edits[start..end];
+        let old_start = edits[..start].iter().filter(|edit| !matches!(edit, Edit::Insert(_))).count();
+        let new_start = edits[..start].iter().filter(|edit| !matches!(edit, Edit::Delete(_))).count();
+        let old_count = hunk.iter().filter(|edit| !matches!(edit, Edit::Insert(_))).count();
+        let new_count = hunk.iter().filter(|edit| !matches!(edit, Edit::Delete(_))).count();
+        out.push_str(&format!("@@ -{} +{} @@\n", format_range(old_start, old_count), format_range(new_start, new_count)));
+        for edit in hunk {
+            match *edit {
+                Edit::Equal(i, _) => push_line(&mut out, ' ', a_lines[i]),
+                Edit::Delete(i) => push_line(&mut out, '-', a_lines[i]),
+                Edit::Insert(j) => push_line(&mut out, '+', b_lines[j]),
+            }
+        }
+    }
+    out
+}
+
+pub fn line_counts(a: &[u8], b: &[u8]) -> (usize, usize) {
+    let edits = diff_lines(&split_lines(a), &split_lines(b));
+    let insertions = edits.iter().filter(|edit| matches!(edit, Edit::Insert(_))).count();
+    let deletions = edits.iter().filter(|edit| matches!(edit, Edit::Delete(_))).count();
+    (insertions, deletions)
+}
+
+fn is_tree(entry: &TreeEntry) -> bool {
+    entry.object_type() == catfile::ObjectType::Tree
+}
+
+fn one_sided<F>(entry: &TreeEntry, path: String, status: ChangeKind, read: &F, out: &mut Vec<TreeChange>) -> anyhow::Result<()>
+where
+    F: Fn(&str) -> anyhow::Result<Vec<TreeEntry>>,
+{
+    if is_tree(entry) {
+        let entries = read(&entry.hash)?;
+        let prefix = format!("{path}/");
+        return match status {
+            ChangeKind::Deleted => diff_entries(&entries, &[], &prefix, read, out),
+            _ => diff_entries(&[], &entries, &prefix, read, out),
+        };
+    }
+    let (mode, hash) = (Some(entry.mode.clone()), Some(entry.hash.clone()));
+    out.push(match status {
+        ChangeKind::Deleted => TreeChange { path, old_mode: mode, new_mode: None, old_hash: hash, new_hash: None, status },
+        _ => TreeChange { path, old_mode: None, new_mode: mode, old_hash: None, new_hash: hash, status },
+    });
+    Ok(())
+}
+
+fn diff_entries<F>(a: &[TreeEntry], b: &[TreeEntry], prefix: &str, read: &F, out: &mut Vec<TreeChange>) -> anyhow::Result<()>
+where
+    F: Fn(&str) -> anyhow::Result<Vec<TreeEntry>>,
+{
+    let (mut i, mut j) = (0, 0);
+    loop {
+        let order = match (a.get(i), b.get(j)) {
+            (None, None) => return Ok(()),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(old), Some(new)) => tree::compare_entries((&old.mode, &old.name), (&new.mode, &new.name)),
+        };
+        match order {
+            Ordering::Less => {
+                one_sided(&a[i], format!("{prefix}{}", a[i].name), ChangeKind::Deleted, read, out)?;
+                i += 1;
+            }
+            Ordering::Greater => {
+                one_sided(&b[j], format!("{prefix}{}", b[j].name), ChangeKind::Added, read, out)?;
+                j += 1;
+            }
+            Ordering::Equal => {
+                let (old, new) = (&a[i], &b[j]);
+                let path = format!("{prefix}{}", old.name);
+                if is_tree(old) && old.hash != new.hash {
+                    diff_entries(&read(&old.hash)?, &read(&new.hash)?, &format!("{path}/"), read, out)?;
+                } else if !is_tree(old) && (old.mode != new.mode || old.hash != new.hash) {
+                    out.push(TreeChange {
+                        path,
+                        old_mode: Some(old.mode.clone()),
+                        new_mode: Some(new.mode.clone()),
+                        old_hash: Some(old.hash.clone()),
+                        new_hash: Some(new.hash.clone()),
+                        status: ChangeKind::Modified,
+                    });
+                }
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+}
+
+/// Compares two sorted tree listings, recursing into subtrees that differ.
+/// Renames show up as a deletion plus an addition.
+pub fn diff_trees(a: &[TreeEntry], b: &[TreeEntry]) -> anyhow::Result<Vec<TreeChange>> {
+    let mut changes = Vec::new();
+    diff_entries(a, b, "", &tree::read_tree, &mut changes)?;
+    Ok(changes)
+}
+
+pub fn diff_blobs(a: &str, b: &str) -> anyhow::Result<()> {
+    let (a, b) = (catfile::resolve_hash(a)?, catfile::resolve_hash(b)?);
+    let diff = unified_diff(&catfile::read_blob(&a)?, &catfile::read_blob(&b)?);
+    if !diff.is_empty() {
+        print!("--- a/{a}\n+++ b/{b}\n{diff}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn entry(mode: &str, name: &str, hash: &str) -> TreeEntry {
+        TreeEntry { mode: mode.to_string(), name: name.to_string(), hash: hash.to_string() }
+    }
+
+    fn diff_with_subtrees(a: &[TreeEntry], b: &[TreeEntry], subtrees: HashMap<&str, Vec<TreeEntry>>) -> anyhow::Result<Vec<(String, ChangeKind)>> {
+        let read = |hash: &str| Ok(subtrees.get(hash).map(|entries| entries.to_vec()).unwrap_or_default());
+        let mut changes = Vec::new();
+        diff_entries(a, b, "", &read, &mut changes)?;
+        Ok(changes.into_iter().map(|change| (change.path, change.status)).collect())
+    }
+
+    #[test]
+    fn unified_diff_with_identical_content_is_empty() {
+        assert_eq!(unified_diff(b"same\n", b"same\n"), "");
+    }
+
+    #[test]
+    fn unified_diff_replaces_a_line_with_context() {
+        let a = b"one\ntwo\nthree\nfour\nfive\n";
+        let b = b"one\ntwo\nTHREE\nfour\nfive\n";
+        let expected_diff = "@@ -1,5 +1,5 @@\n one\n two\n-three\n+THREE\n four\n five\n";
+
+        assert_eq!(unified_diff(a, b), expected_diff);
+    }
+
+    #[test]
+    fn unified_diff_splits_distant_changes_into_hunks() {
+        let a = b"1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let b = b"one\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\ntwelve\n";
+        let expected_diff = "@@ -1,4 +1,4 @@\n-1\n+one\n 2\n 3\n 4\n@@ -9,4 +9,4 @@\n 9\n 10\n 11\n-12\n+twelve\n";
+
+        assert_eq!(unified_diff(a, b), expected_diff);
+    }
+
+    #[test]
+    fn unified_diff_keeps_deletions_before_insertions() {
+        let a = b"keep\na\nb\nc\nend\n";
+        let b = b"keep\nx\nb\ny\nz\nend\n";
+        let expected_diff = "@@ -1,5 +1,6 @@\n keep\n-a\n+x\n b\n-c\n+y\n+z\n end\n";
+
+        assert_eq!(unified_diff(a, b), expected_diff);
+    }
+
+    #[test]
+    fn line_counts_for_large_files() {
+        let a: String = (0..50_000).map(|n| format!("line {n}\n")).collect();
+        let b: String = (0..50_000).map(|n| if n % 1000 == 500 { format!("changed {n}\n") } else { format!("line {n}\n") }).collect();
+
+        assert_eq!(line_counts(a.as_bytes(), b.as_bytes()), (50, 50));
+        assert_eq!(line_counts(a.as_bytes(), b""), (0, 50_000));
+    }
+
+    #[test]
+    fn line_counts_are_minimal() {
+        assert_eq!(line_counts(b"a\nb\nc\na\nb\nb\na\n", b"c\nb\na\nb\na\nc\n"), (2, 3));
+        assert_eq!(line_counts(b"1\n2\n3\n", b"4\n5\n"), (2, 3));
+    }
+
+    #[test]
+    fn unified_diff_from_empty_content() {
+        assert_eq!(unified_diff(b"", b"new\n"), "@@ -0,0 +1 @@\n+new\n");
+    }
+
+    #[test]
+    fn unified_diff_marks_missing_trailing_newline() {
+        let expected_diff = "@@ -1 +1 @@\n-old\n\\ No newline at end of file\n+new\n";
+
+        assert_eq!(unified_diff(b"old", b"new\n"), expected_diff);
+    }
+
+    #[test]
+    fn unified_diff_with_binary_content() {
+        assert_eq!(unified_diff(b"text\n", b"bin\0ary"), "Binary files differ\n");
+    }
+
+    #[test]
+    fn is_binary_with_text_blob() {
+        assert!(!is_binary(b"fn main() {}\n"));
+    }
+
+    #[test]
+    fn is_binary_with_embedded_nul() {
+        assert!(is_binary(b"PNG\0\x01\x02"));
+    }
+
+    #[test]
+    fn is_binary_with_empty_blob() {
+        assert!(!is_binary(b""));
+    }
+
+    #[test]
+    fn is_binary_only_checks_prefix() {
+        let mut content = vec![b'a'; BINARY_CHECK_LEN];
+        content.push(0);
+
+        assert!(!is_binary(&content));
+    }
+
+    #[test]
+    fn diff_trees_reports_flat_changes_in_tree_order() -> anyhow::Result<()> {
+        let a = [entry("100644", "a", "1"), entry("100644", "b", "2"), entry("100644", "c", "3")];
+        let b = [entry("100644", "a", "1"), entry("100644", "b", "9"), entry("100644", "d", "4")];
+        let expected_changes = vec![
+            ("b".to_string(), ChangeKind::Modified),
+            ("c".to_string(), ChangeKind::Deleted),
+            ("d".to_string(), ChangeKind::Added),
+        ];
+
+        assert_eq!(diff_with_subtrees(&a, &b, HashMap::new())?, expected_changes);
+        Ok(())
+    }
+
+    #[test]
+    fn diff_trees_reports_mode_only_changes() -> anyhow::Result<()> {
+        let a = [entry("100644", "run.sh", "1")];
+        let b = [entry("100755", "run.sh", "1")];
+
+        assert_eq!(diff_with_subtrees(&a, &b, HashMap::new())?, [("run.sh".to_string(), ChangeKind::Modified)]);
+        Ok(())
+    }
+
+    #[test]
+    fn diff_trees_recurses_into_changed_and_one_sided_subtrees() -> anyhow::Result<()> {
+        let a = [entry("40000", "old", "o"), entry("40000", "src", "s1")];
+        let b = [entry("40000", "new", "n"), entry("40000", "src", "s2")];
+        let subtrees = HashMap::from([
+            ("s1", vec![entry("100644", "lib.rs", "1"), entry("100644", "same.rs", "2")]),
+            ("s2", vec![entry("100644", "lib.rs", "3"), entry("100644", "same.rs", "2")]),
+            ("o", vec![entry("100644", "gone", "4")]),
+            ("n", vec![entry("100644", "fresh", "5")]),
+        ]);
+        let expected_changes = vec![
+            ("new/fresh".to_string(), ChangeKind::Added),
+            ("old/gone".to_string(), ChangeKind::Deleted),
+            ("src/lib.rs".to_string(), ChangeKind::Modified),
+        ];
+
+        assert_eq!(diff_with_subtrees(&a, &b, subtrees)?, expected_changes);
+        Ok(())
+    }
+
+    #[test]
+    fn diff_trees_reports_file_replaced_by_directory_as_delete_and_add() -> anyhow::Result<()> {
+        let a = [entry("100644", "x", "1")];
+        let b = [entry("40000", "x", "t")];
+        let subtrees = HashMap::from([("t", vec![entry("100644", "y", "2")])]);
+        let expected_changes = vec![("x".to_string(), ChangeKind::Deleted), ("x/y".to_string(), ChangeKind::Added)];
+
+        assert_eq!(diff_with_subtrees(&a, &b, subtrees)?, expected_changes);
+        Ok(())
+    }
+}
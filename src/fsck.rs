@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use crate::catfile;
+use crate::catfile::ObjectFormat;
+use crate::repo;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("fsck found {0} problem(s)")]
+    ProblemsFound(usize),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Problem {
+    HashMismatch(String),
+    Corrupt,
+}
+
+fn is_hex(name: &str, len: usize) -> bool {
+    name.len() == len && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+pub(crate) fn loose_objects(objects_dir: &Path, format: ObjectFormat) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut objects = Vec::new();
+    for dir in fs::read_dir(objects_dir)? {
+        let dir = dir?;
+        let dir_name = dir.file_name().to_string_lossy().into_owned();
+        if !is_hex(&dir_name, 2) || !dir.file_type()?.is_dir() {
+            continue;
+        }
+        for file in fs::read_dir(dir.path())? {
+            let file = file?;
+            let file_name = file.file_name().to_string_lossy().into_owned();
+            if is_hex(&file_name, format.hex_len() - 2) {
+                objects.push((format!("{dir_name}{file_name}"), file.path()));
+            }
+        }
+    }
+    objects.sort();
+    Ok(objects)
+}
+
+fn check_object(path: &Path, hash: &str, format: ObjectFormat) -> Option<Problem> {
+    let Ok((object_type, content)) = catfile::read_loose_object(path) else {
+        return Some(Problem::Corrupt);
+    };
+    let actual = format.hash(&catfile::object_payload(object_type, &content));
+    (actual != hash).then_some(Problem::HashMismatch(actual))
+}
+
+pub fn fsck() -> anyhow::Result<()> {
+    let git_dir = repo::git_dir()?;
+    let format = ObjectFormat::of(&git_dir);
+    let objects = loose_objects(&git_dir.join("objects"), format)?;
+    let mut problems = 0;
+    for (hash, path) in &objects {
+        match check_object(path, hash, format) {
+            Some(Problem::HashMismatch(actual)) => eprintln!("error: hash mismatch for {hash} (computed {actual})"),
+            Some(Problem::Corrupt) => eprintln!("error: corrupt object {hash}"),
+            None => continue,
+        }
+        problems += 1;
+    }
+    println!("Checked {} objects", objects.len());
+    if problems > 0 {
+        return Err(Error::ProblemsFound(problems).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+
+    const BLOB_HASH: &str = "bd9dbf5aae1a3862dd1526723246b20206e5fc37";
+
+    fn write_compressed(path: &Path, payload: &[u8]) {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(payload).unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, encoder.finish().unwrap()).unwrap();
+    }
+
+    fn objects_dir(name: &str) -> PathBuf {
+        let objects_dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&objects_dir);
+        objects_dir
+    }
+
+    #[test]
+    fn check_object_accepts_valid_object() {
+        let path = objects_dir("fsck_valid_object").join("bd/9dbf5aae1a3862dd1526723246b20206e5fc37");
+        write_compressed(&path, b"blob 16\0what is up, doc?");
+
+        assert_eq!(check_object(&path, BLOB_HASH, ObjectFormat::Sha1), None);
+    }
+
+    #[test]
+    fn check_object_reports_hash_mismatch() {
+        let path = objects_dir("fsck_hash_mismatch").join("bd/9dbf5aae1a3862dd1526723246b20206e5fc37");
+        write_compressed(&path, b"blob 16\0what is up, dog?");
+
+        let problem = check_object(&path, BLOB_HASH, ObjectFormat::Sha1);
+        assert!(matches!(problem, Some(Problem::HashMismatch(actual)) if actual != BLOB_HASH));
+    }
+
+    #[test]
+    fn check_object_reports_corrupt_object() {
+        let path = objects_dir("fsck_corrupt_object").join("bd/9dbf5aae1a3862dd1526723246b20206e5fc37");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "not zlib data").unwrap();
+
+        assert_eq!(check_object(&path, BLOB_HASH, ObjectFormat::Sha1), Some(Problem::Corrupt));
+    }
+
+    #[test]
+    fn loose_objects_skips_non_object_entries() -> anyhow::Result<()> {
+        let objects_dir = objects_dir("fsck_loose_objects");
+        write_compressed(&objects_dir.join("bd/9dbf5aae1a3862dd1526723246b20206e5fc37"), b"");
+        write_compressed(&objects_dir.join("bd/tmp_obj_123"), b"");
+        write_compressed(&objects_dir.join("pack/pack-1234.idx"), b"");
+
+        let objects = loose_objects(&objects_dir, ObjectFormat::Sha1)?;
+        let hashes: Vec<&str> = objects.iter().map(|(hash, _)| hash.as_str()).collect();
+        assert_eq!(hashes, vec![BLOB_HASH]);
+        Ok(())
+    }
+}
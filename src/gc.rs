@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::Path;
+use crate::catfile;
+use crate::catfile::ObjectFormat;
+use crate::fsck;
+use crate::pack;
+use crate::repo;
+use crate::Verbosity;
+
+fn remove_loose_object(path: &Path) -> std::io::Result<()> {
+    fs::remove_file(path)?;
+    if let Some(dir) = path.parent() {
+        if fs::read_dir(dir)?.next().is_none() {
+            fs::remove_dir(dir)?;
+        }
+    }
+    Ok(())
+}
+
+fn pack_loose_objects(objects_dir: &Path, format: ObjectFormat, prune: bool) -> anyhow::Result<usize> {
+    let loose = fsck::loose_objects(objects_dir, format)?;
+    if loose.is_empty() {
+        return Ok(0);
+    }
+    let objects = loose
+        .iter()
+        .map(|(_, path)| catfile::read_loose_object(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (data, packed) = pack::write_pack(&objects, format)?;
+    let pack_dir = objects_dir.join("pack");
+    fs::create_dir_all(&pack_dir)?;
+    pack::write_pack_files(&pack_dir.join("pack"), &data, &packed, format)?;
+    if prune {
+        for (_, path) in &loose {
+            remove_loose_object(path)?;
+        }
+    }
+    Ok(loose.len())
+}
+
+pub fn gc(prune: bool, verbosity: Verbosity) -> anyhow::Result<()> {
+    let git_dir = repo::git_dir()?;
+    let count = pack_loose_objects(&git_dir.join("objects"), ObjectFormat::of(&git_dir), prune)?;
+    if !verbosity.is_quiet() {
+        eprintln!("Packed {count} objects");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::io::Write;
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+
+    fn write_loose(objects_dir: &Path, payload: &[u8]) -> String {
+        let hash = ObjectFormat::Sha1.hash(payload);
+        let path = objects_dir.join(&hash[..2]).join(&hash[2..]);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(payload).unwrap();
+        fs::write(path, encoder.finish().unwrap()).unwrap();
+        hash
+    }
+
+    #[test]
+    fn pack_loose_objects_packs_and_prunes() -> anyhow::Result<()> {
+        let objects_dir = env::temp_dir().join("gc_pack_loose_objects");
+        let _ = fs::remove_dir_all(&objects_dir);
+        let hash = write_loose(&objects_dir, b"blob 11\0hello world");
+
+        assert_eq!(pack_loose_objects(&objects_dir, ObjectFormat::Sha1, true)?, 1);
+        assert!(!objects_dir.join(&hash[..2]).exists());
+        let object = pack::find_packed_object(&objects_dir, &hash, ObjectFormat::Sha1)?;
+        assert_eq!(object, Some((catfile::ObjectType::Blob, b"hello world".to_vec())));
+        Ok(())
+    }
+}
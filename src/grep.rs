@@ -0,0 +1,108 @@
+use crate::catfile;
+use crate::catfile::{ObjectType, ParseMode};
+use crate::commit;
+use crate::diff;
+use crate::index;
+use crate::refs;
+use crate::tag;
+use crate::tree;
+
+fn collect_blobs(hash: &str, prefix: &str, blobs: &mut Vec<(String, String)>) -> anyhow::Result<()> {
+    for entry in tree::read_tree(hash)? {
+        let path = format!("{prefix}{}", entry.name);
+        match entry.object_type() {
+            ObjectType::Tree => collect_blobs(&entry.hash, &format!("{path}/"), blobs)?,
+            ObjectType::Blob => blobs.push((path, entry.hash)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn tracked_blobs(rev: Option<&str>) -> anyhow::Result<Vec<(String, String)>> {
+    let mut blobs = Vec::new();
+    match rev {
+        Some(rev) => {
+            let hash = tag::peel_tag(&refs::rev_parse(rev)?)?;
+            collect_blobs(&commit::parse_commit(&hash)?.tree, "", &mut blobs)?;
+        }
+        None => {
+            for entry in index::read_index()? {
+                if entry.stage() == 0 && entry.mode != 0o160000 {
+                    blobs.push((entry.path, hex::encode(entry.hash)));
+                }
+            }
+        }
+    }
+    Ok(blobs)
+}
+
+fn matching_lines(content: &[u8], pattern: &str, ignore_case: bool) -> Vec<(usize, String)> {
+    let pattern = if ignore_case { pattern.to_lowercase() } else { pattern.to_string() };
+    let content = content.strip_suffix(b"\n").unwrap_or(content);
+    content
+        .split(|&byte| byte == b'\n')
+        .map(String::from_utf8_lossy)
+        .enumerate()
+        .filter(|(_, line)| match ignore_case {
+            true => line.to_lowercase().contains(&pattern),
+            false => line.contains(&pattern),
+        })
+        .map(|(number, line)| (number + 1, line.into_owned()))
+        .collect()
+}
+
+fn format_match(rev: Option<&str>, path: &str, number: usize, line: &str, line_numbers: bool) -> String {
+    let mut output = String::new();
+    if let Some(rev) = rev {
+        output.push_str(&format!("{rev}:"));
+    }
+    output.push_str(&format!("{path}:"));
+    if line_numbers {
+        output.push_str(&format!("{number}:"));
+    }
+    output.push_str(line);
+    output
+}
+
+pub fn grep(pattern: &str, rev: Option<&str>, line_numbers: bool, ignore_case: bool) -> anyhow::Result<bool> {
+    let mut found = false;
+    for (path, hash) in tracked_blobs(rev)? {
+        let (_, content) = catfile::read_object(&hash, ParseMode::Strict)?;
+        if diff::is_binary(&content) {
+            continue;
+        }
+        for (number, line) in matching_lines(&content, pattern, ignore_case) {
+            println!("{}", format_match(rev, &path, number, &line, line_numbers));
+            found = true;
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matching_lines_numbers_from_one() {
+        let content = b"fn main() {\n    println!(\"hi\");\n}\n";
+        let expected_lines = vec![(2, "    println!(\"hi\");".to_string())];
+
+        assert_eq!(matching_lines(content, "println", false), expected_lines);
+    }
+
+    #[test]
+    fn matching_lines_ignores_case_when_asked() {
+        let content = b"TODO: one\ntodo: two\ndone\n";
+
+        assert_eq!(matching_lines(content, "todo", false).len(), 1);
+        assert_eq!(matching_lines(content, "todo", true).len(), 2);
+    }
+
+    #[test]
+    fn format_match_with_rev_and_line_numbers() {
+        assert_eq!(format_match(Some("HEAD"), "src/main.rs", 3, "let x;", true), "HEAD:src/main.rs:3:let x;");
+        assert_eq!(format_match(None, "src/main.rs", 3, "let x;", false), "src/main.rs:let x;");
+    }
+}
@@ -0,0 +1,509 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use crate::catfile;
+use crate::catfile::{ObjectFormat, ObjectType};
+use crate::repo;
+use crate::status;
+use crate::tree;
+use crate::Verbosity;
+
+const SIGNATURE: &[u8; 4] = b"DIRC";
+const VERSION: u32 = 2;
+const NAME_MASK: u16 = 0x0fff;
+const EXTENDED_FLAG: u16 = 0x4000;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("malformed index file")]
+    MalformedIndex,
+    #[error("unsupported index version: {0}")]
+    UnsupportedVersion(u32),
+    #[error("index checksum mismatch")]
+    ChecksumMismatch,
+    #[error("pathspec '{0}' did not match any files")]
+    PathNotFound(String),
+    #[error("'{0}' is outside repository")]
+    OutsideRepository(String),
+    #[error("'{0}' has changes staged in the index (use --cached to keep the file, or -f to force removal)")]
+    StagedChanges(String),
+    #[error("'{0}' has local modifications (use --cached to keep the file, or -f to force removal)")]
+    LocalModifications(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub ctime_seconds: u32,
+    pub ctime_nanoseconds: u32,
+    pub mtime_seconds: u32,
+    pub mtime_nanoseconds: u32,
+    pub dev: u32,
+    pub ino: u32,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u32,
+    pub hash: Vec<u8>,
+    pub flags: u16,
+    pub path: String,
+}
+
+impl IndexEntry {
+    pub fn stage(&self) -> u16 {
+        (self.flags >> 12) & 0x3
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn from_metadata(path: String, hash: Vec<u8>, metadata: &fs::Metadata) -> IndexEntry {
+        use std::os::unix::fs::MetadataExt;
+        IndexEntry {
+            ctime_seconds: metadata.ctime() as u32,
+            ctime_nanoseconds: metadata.ctime_nsec() as u32,
+            mtime_seconds: metadata.mtime() as u32,
+            mtime_nanoseconds: metadata.mtime_nsec() as u32,
+            dev: metadata.dev() as u32,
+            ino: metadata.ino() as u32,
+            mode: if metadata.file_type().is_symlink() {
+                0o120000
+            } else if tree::is_executable(metadata) {
+                0o100755
+            } else {
+                0o100644
+            },
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            size: metadata.size() as u32,
+            flags: path.len().min(NAME_MASK as usize) as u16,
+            hash,
+            path,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn from_metadata(path: String, hash: Vec<u8>, metadata: &fs::Metadata) -> IndexEntry {
+        IndexEntry {
+            mode: 0o100644,
+            size: metadata.len() as u32,
+            flags: path.len().min(NAME_MASK as usize) as u16,
+            hash,
+            path,
+            ..IndexEntry::default()
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self.data.get(self.offset..self.offset + len).ok_or(Error::MalformedIndex)?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+fn parse_entry(reader: &mut Reader, version: u32, hash_len: usize) -> Result<IndexEntry, Error> {
+    let start = reader.offset;
+    let mut entry = IndexEntry {
+        ctime_seconds: reader.u32()?,
+        ctime_nanoseconds: reader.u32()?,
+        mtime_seconds: reader.u32()?,
+        mtime_nanoseconds: reader.u32()?,
+        dev: reader.u32()?,
+        ino: reader.u32()?,
+        mode: reader.u32()?,
+        uid: reader.u32()?,
+        gid: reader.u32()?,
+        size: reader.u32()?,
+        hash: reader.take(hash_len)?.to_vec(),
+        flags: reader.u16()?,
+        path: String::new(),
+    };
+    if version >= 3 && entry.flags & EXTENDED_FLAG != 0 {
+        reader.u16()?;
+    }
+    let rest = &reader.data[reader.offset..];
+    let name_len = rest.iter().position(|&b| b == 0).ok_or(Error::MalformedIndex)?;
+    entry.path = String::from_utf8(reader.take(name_len)?.to_vec()).map_err(|_|Error::MalformedIndex)?;
+    let entry_len = reader.offset - start;
+    reader.take((entry_len + 8) / 8 * 8 - entry_len)?;
+    Ok(entry)
+}
+
+fn parse_index(data: &[u8], format: ObjectFormat) -> Result<Vec<IndexEntry>, Error> {
+    let hash_len = format.raw_len();
+    if data.len() < 12 + hash_len {
+        return Err(Error::MalformedIndex);
+    }
+    let (content, checksum) = data.split_at(data.len() - hash_len);
+    if format.digest(content) != checksum {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    let mut reader = Reader { data: content, offset: 0 };
+    if reader.take(4)? != SIGNATURE {
+        return Err(Error::MalformedIndex);
+    }
+    let version = reader.u32()?;
+    if !(2..=3).contains(&version) {
+        return Err(Error::UnsupportedVersion(version));
+    }
+    let count = reader.u32()?;
+    (0..count).map(|_| parse_entry(&mut reader, version, hash_len)).collect()
+}
+
+fn serialize_index(entries: &[IndexEntry], format: ObjectFormat) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(SIGNATURE);
+    data.extend_from_slice(&VERSION.to_be_bytes());
+    data.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    for entry in entries {
+        let start = data.len();
+        for field in [
+            entry.ctime_seconds, entry.ctime_nanoseconds, entry.mtime_seconds, entry.mtime_nanoseconds,
+            entry.dev, entry.ino, entry.mode, entry.uid, entry.gid, entry.size,
+        ] {
+            data.extend_from_slice(&field.to_be_bytes());
+        }
+        data.extend_from_slice(&entry.hash);
+        data.extend_from_slice(&(entry.flags & !EXTENDED_FLAG).to_be_bytes());
+        data.extend_from_slice(entry.path.as_bytes());
+        let entry_len = data.len() - start;
+        data.resize(start + (entry_len + 8) / 8 * 8, 0);
+    }
+    let checksum = format.digest(&data);
+    data.extend_from_slice(&checksum);
+    data
+}
+
+fn insert_entry(entries: &mut Vec<IndexEntry>, entry: IndexEntry) {
+    entries.retain(|existing| existing.path != entry.path);
+    entries.push(entry);
+    entries.sort_by(|a, b| a.path.as_bytes().cmp(b.path.as_bytes()).then(a.stage().cmp(&b.stage())));
+}
+
+pub fn read_index() -> anyhow::Result<Vec<IndexEntry>> {
+    let git_dir = repo::git_dir()?;
+    match fs::read(git_dir.join("index")) {
+        Ok(data) => Ok(parse_index(&data, ObjectFormat::of(&git_dir))?),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn write_index(entries: &[IndexEntry]) -> anyhow::Result<()> {
+    let git_dir = repo::git_dir()?;
+    fs::write(git_dir.join("index"), serialize_index(entries, ObjectFormat::of(&git_dir)))?;
+    Ok(())
+}
+
+fn format_ls_entry(entry: &IndexEntry, stage: bool) -> String {
+    if !stage {
+        return entry.path.clone();
+    }
+    format!("{:06o} {} {}\t{}", entry.mode, hex::encode(&entry.hash), entry.stage(), entry.path)
+}
+
+pub fn ls_files(stage: bool) -> anyhow::Result<()> {
+    let mut entries = read_index()?;
+    entries.sort_by(|a, b| a.path.as_bytes().cmp(b.path.as_bytes()).then(a.stage().cmp(&b.stage())));
+    for entry in &entries {
+        println!("{}", format_ls_entry(entry, stage));
+    }
+    Ok(())
+}
+
+fn index_path(work_tree: &Path, path: &Path) -> anyhow::Result<String> {
+    let not_found = || Error::PathNotFound(path.display().to_string());
+    let absolute = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+            fs::canonicalize(parent).map_err(|_|not_found())?.join(name)
+        }
+        _ => fs::canonicalize(path).map_err(|_|not_found())?,
+    };
+    let relative = absolute
+        .strip_prefix(work_tree)
+        .map_err(|_|Error::OutsideRepository(path.display().to_string()))?;
+    Ok(relative.to_string_lossy().replace('\\', "/"))
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    if !fs::symlink_metadata(path)?.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_name() != ".git" {
+            collect_files(&entry.path(), files)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn add(paths: &[String]) -> anyhow::Result<()> {
+    let work_tree = fs::canonicalize(repo::work_tree()?)?;
+    let mut entries = read_index()?;
+    for path in paths {
+        let path = Path::new(path);
+        if fs::symlink_metadata(path).is_err() {
+            return Err(Error::PathNotFound(path.display().to_string()).into());
+        }
+        let mut files = Vec::new();
+        collect_files(path, &mut files)?;
+        for file in files {
+            let hash = catfile::write_object_content(ObjectType::Blob, &catfile::read_worktree_file(&file)?)?;
+            let entry = IndexEntry::from_metadata(index_path(&work_tree, &file)?, hex::decode(hash)?, &fs::symlink_metadata(&file)?);
+            insert_entry(&mut entries, entry);
+        }
+    }
+    write_index(&entries)
+}
+
+fn check_removable(path: &str, staged: &str, head: Option<&str>, worktree: Option<&str>, cached: bool) -> Result<(), Error> {
+    let committed = head == Some(staged);
+    if cached && worktree == Some(staged) {
+        return Ok(());
+    }
+    if !committed {
+        return Err(Error::StagedChanges(path.to_string()));
+    }
+    if !cached && worktree.is_some_and(|worktree| worktree != staged) {
+        return Err(Error::LocalModifications(path.to_string()));
+    }
+    Ok(())
+}
+
+pub fn rm(paths: &[String], cached: bool, force: bool, verbosity: Verbosity) -> anyhow::Result<()> {
+    let work_tree = fs::canonicalize(repo::work_tree()?)?;
+    let mut entries = read_index()?;
+    let head = status::head_files()?;
+    let mut removed = Vec::new();
+    for path in paths {
+        let tracked = index_path(&work_tree, Path::new(path))?;
+        let entry = entries.iter().find(|entry| entry.path == tracked).ok_or_else(|| Error::PathNotFound(path.clone()))?;
+        if !force {
+            let file = work_tree.join(&tracked);
+            let worktree = catfile::hash_file(&file.to_string_lossy()).ok();
+            check_removable(&tracked, &hex::encode(&entry.hash), head.get(&tracked).map(String::as_str), worktree.as_deref(), cached)?;
+        }
+        removed.push(tracked);
+    }
+
+    entries.retain(|entry| !removed.contains(&entry.path));
+    write_index(&entries)?;
+    for path in &removed {
+        if !cached {
+            match fs::remove_file(work_tree.join(path)) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+        if !verbosity.is_quiet() {
+            println!("rm '{path}'");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(path: &str) -> IndexEntry {
+        IndexEntry {
+            ctime_seconds: 1700000000,
+            mtime_seconds: 1700000001,
+            mode: 0o100644,
+            size: 3,
+            hash: vec![0xab; 20],
+            flags: path.len() as u16,
+            path: path.to_string(),
+            ..IndexEntry::default()
+        }
+    }
+
+    #[test]
+    fn serialize_index_round_trips_through_parse_index() -> Result<(), Error> {
+        let entries = vec![entry("a.txt"), entry("src/main.rs"), entry("src/some/longer/path.rs")];
+
+        let data = serialize_index(&entries, ObjectFormat::Sha1);
+        assert_eq!(parse_index(&data, ObjectFormat::Sha1)?, entries);
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_index_writes_header_and_padded_entries() {
+        let data = serialize_index(&[entry("abcdefgh")], ObjectFormat::Sha1);
+
+        assert_eq!(&data[..12], b"DIRC\0\0\0\x02\0\0\0\x01");
+        // 62 fixed bytes + 8 name bytes, padded with at least one NUL to a multiple of 8
+        assert_eq!(data.len(), 12 + 72 + 20);
+        assert_eq!(&data[12 + 62..12 + 72], b"abcdefgh\0\0");
+    }
+
+    #[test]
+    fn parse_index_is_error_with_bad_checksum() {
+        let mut data = serialize_index(&[entry("a.txt")], ObjectFormat::Sha1);
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        let expected_error = Err(Error::ChecksumMismatch);
+
+        let actual_result = parse_index(&data, ObjectFormat::Sha1);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn parse_index_is_error_with_bad_signature() {
+        let mut data = b"DIRX\0\0\0\x02\0\0\0\0".to_vec();
+        data.extend_from_slice(&ObjectFormat::Sha1.digest(&data));
+        let expected_error = Err(Error::MalformedIndex);
+
+        let actual_result = parse_index(&data, ObjectFormat::Sha1);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn insert_entry_replaces_and_sorts() {
+        let mut entries = vec![entry("b.txt"), entry("d.txt")];
+        let mut updated = entry("b.txt");
+        updated.size = 10;
+
+        insert_entry(&mut entries, entry("a.txt"));
+        insert_entry(&mut entries, updated.clone());
+        let paths: Vec<&str> = entries.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["a.txt", "b.txt", "d.txt"]);
+        assert_eq!(entries[1], updated);
+    }
+
+    #[test]
+    fn format_ls_entry_with_stage() {
+        let mut conflicted = entry("src/main.rs");
+        conflicted.flags |= 2 << 12;
+        let expected_line = format!("100644 {} 2\tsrc/main.rs", "ab".repeat(20));
+
+        assert_eq!(format_ls_entry(&conflicted, true), expected_line);
+        assert_eq!(format_ls_entry(&conflicted, false), "src/main.rs");
+    }
+
+    #[test]
+    fn parse_index_reads_names_of_every_padding_length() -> Result<(), Error> {
+        let entries: Vec<IndexEntry> = (1..=9).map(|len| entry(&"x".repeat(len))).collect();
+
+        let parsed = parse_index(&serialize_index(&entries, ObjectFormat::Sha1), ObjectFormat::Sha1)?;
+        assert_eq!(parsed, entries);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn group_executable_file_is_staged_as_regular_file() -> std::io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join("group_executable_index_mode");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("script.sh");
+        fs::write(&path, "#!/bin/sh\n")?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o654))?;
+
+        let metadata = fs::metadata(&path)?;
+        let entry = IndexEntry::from_metadata("script.sh".to_string(), vec![0; 20], &metadata);
+        assert_eq!(format!("{:o}", entry.mode), tree::file_mode(&metadata));
+        assert_eq!(entry.mode, 0o100644);
+        Ok(())
+    }
+
+    #[test]
+    fn check_removable_allows_committed_content() {
+        assert_eq!(check_removable("a.txt", "aa", Some("aa"), Some("aa"), false), Ok(()));
+        assert_eq!(check_removable("a.txt", "aa", Some("aa"), None, false), Ok(()));
+    }
+
+    #[test]
+    fn check_removable_refuses_local_modifications() {
+        let expected_error = Err(Error::LocalModifications("a.txt".to_string()));
+
+        assert_eq!(check_removable("a.txt", "aa", Some("aa"), Some("bb"), false), expected_error);
+        assert_eq!(check_removable("a.txt", "aa", Some("aa"), Some("bb"), true), Ok(()));
+    }
+
+    #[test]
+    fn check_removable_refuses_staged_changes() {
+        let expected_error = Err(Error::StagedChanges("a.txt".to_string()));
+
+        assert_eq!(check_removable("a.txt", "bb", Some("aa"), Some("bb"), false), expected_error);
+        assert_eq!(check_removable("a.txt", "bb", None, Some("cc"), true), expected_error);
+    }
+
+    #[test]
+    fn check_removable_with_cached_allows_content_matching_worktree() {
+        assert_eq!(check_removable("a.txt", "bb", None, Some("bb"), true), Ok(()));
+    }
+
+    #[test]
+    fn added_crlf_text_file_is_normalized_and_clean_in_status() -> anyhow::Result<()> {
+        let _lock = repo::ENV_LOCK.lock().unwrap();
+        let root = std::env::temp_dir().join("add_crlf_text_file");
+        let _ = fs::remove_dir_all(&root);
+        let git_dir = root.join(".git");
+        fs::create_dir_all(git_dir.join("objects"))?;
+        fs::create_dir_all(git_dir.join("refs/heads"))?;
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n")?;
+        fs::write(root.join(".gitattributes"), "*.txt text\n")?;
+        fs::write(root.join("a.txt"), "one\r\ntwo\r\n")?;
+        std::env::set_var("GIT_DIR", &git_dir);
+        std::env::set_var("GIT_WORK_TREE", &root);
+
+        let result = (|| {
+            add(&[root.join("a.txt").to_string_lossy().into_owned()])?;
+            let staged = hex::encode(&read_index()?[0].hash);
+            Ok::<_, anyhow::Error>((catfile::read_blob(&staged)?, status::collect_status()?))
+        })();
+        std::env::remove_var("GIT_DIR");
+        std::env::remove_var("GIT_WORK_TREE");
+
+        let (content, status) = result?;
+        assert_eq!(content, b"one\ntwo\n");
+        assert_eq!(status.staged, vec![(status::ChangeKind::Added, "a.txt".to_string())]);
+        assert!(status.unstaged.is_empty());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_stages_symlinks_without_following_them() -> anyhow::Result<()> {
+        let _lock = repo::ENV_LOCK.lock().unwrap();
+        let root = std::env::temp_dir().join("add_symlinks_as_leaves");
+        let _ = fs::remove_dir_all(&root);
+        let git_dir = root.join(".git");
+        fs::create_dir_all(git_dir.join("objects"))?;
+        fs::create_dir_all(root.join("dir"))?;
+        fs::write(root.join("dir/file.txt"), "inside\n")?;
+        std::os::unix::fs::symlink("dir", root.join("link-to-dir"))?;
+        std::os::unix::fs::symlink("missing", root.join("dangling"))?;
+        std::env::set_var("GIT_DIR", &git_dir);
+        std::env::set_var("GIT_WORK_TREE", &root);
+
+        let result = (|| {
+            add(&[root.join("link-to-dir").to_string_lossy().into_owned(), root.join("dangling").to_string_lossy().into_owned()])?;
+            read_index()
+        })();
+        std::env::remove_var("GIT_DIR");
+        std::env::remove_var("GIT_WORK_TREE");
+
+        let staged: Vec<(String, u32)> = result?.into_iter().map(|entry| (entry.path, entry.mode)).collect();
+        assert_eq!(staged, [("dangling".to_string(), 0o120000), ("link-to-dir".to_string(), 0o120000)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use serde::Serialize;
+use thiserror::Error;
+use crate::catfile;
+use crate::color;
+use crate::color::{Color, ColorChoice};
+use crate::commit;
+use crate::commit::Commit;
+use crate::refs;
+use crate::repo;
+use crate::tag;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum PrettyFormat {
+    Full,
+    Oneline,
+    Raw,
+    Json,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("your current branch does not have any commits yet")]
+    NoCommits,
+}
+
+pub(crate) fn head_commit() -> anyhow::Result<String> {
+    match refs::resolve_ref("HEAD") {
+        Err(err) if matches!(err.downcast_ref(), Some(refs::Error::RefNotFound(_))) => Err(Error::NoCommits.into()),
+        result => result,
+    }
+}
+
+pub(crate) fn format_commit(hash: &str, commit: &Commit) -> String {
+    let mut output = format!("commit {hash}\n");
+    match commit::split_signature(&commit.author) {
+        Some((identity, timestamp, offset)) => {
+            output.push_str(&format!("Author: {identity}\n"));
+            if let Some(date) = commit::format_date(timestamp, offset) {
+                output.push_str(&format!("Date:   {date}\n"));
+            }
+        }
+        None => output.push_str(&format!("Author: {}\n", commit.author)),
+    }
+    output.push('\n');
+    for line in commit.message.lines() {
+        output.push_str(&format!("    {line}\n"));
+    }
+    output
+}
+
+fn format_raw(hash: &str, commit: &Commit) -> String {
+    let mut output = format!("commit {hash}\ntree {}\n", commit.tree);
+    for parent in &commit.parents {
+        output.push_str(&format!("parent {parent}\n"));
+    }
+    output.push_str(&format!("author {}\ncommitter {}\n\n", commit.author, commit.committer));
+    for line in commit.message.lines() {
+        output.push_str(&format!("    {line}\n"));
+    }
+    output
+}
+
+/// A commit as printed by `log --pretty=json`: its hash followed by the
+/// fields of the commit itself.
+#[derive(Serialize)]
+struct LogEntry<'a> {
+    hash: &'a str,
+    #[serde(flatten)]
+    commit: &'a Commit,
+}
+
+fn format_json(hash: &str, commit: &Commit) -> serde_json::Result<String> {
+    Ok(serde_json::to_string(&LogEntry { hash, commit })? + "\n")
+}
+
+fn format_oneline(hash: &str, commit: &Commit) -> String {
+    let subject = commit.message.lines().next().unwrap_or("");
+    format!("{hash} {subject}\n")
+}
+
+pub fn log(revision: Option<&str>, pretty: PrettyFormat, abbrev: Option<usize>, color_choice: ColorChoice) -> anyhow::Result<()> {
+    let use_color = color::enabled(color_choice);
+    let start = match revision {
+        Some(revision) => tag::peel_tag(&refs::rev_parse(revision)?)?,
+        None => head_commit()?,
+    };
+    let shallow = repo::shallow_commits()?;
+    let mut next = Some(start);
+    let mut first = true;
+    while let Some(hash) = next {
+        let commit = commit::parse_commit_with(&hash, &shallow)?;
+        let shown = abbrev.map(|n| catfile::abbreviate(&hash, n)).unwrap_or_else(|| hash.clone());
+        let shown = color::paint(&shown, Color::Yellow, use_color);
+        match pretty {
+            PrettyFormat::Full | PrettyFormat::Raw => {
+                if !first {
+                    println!();
+                }
+                let format = if pretty == PrettyFormat::Raw { format_raw } else { format_commit };
+                print!("{}", format(&shown, &commit));
+            }
+            PrettyFormat::Oneline => print!("{}", format_oneline(&shown, &commit)),
+            PrettyFormat::Json => print!("{}", format_json(&hash, &commit)?),
+        }
+        first = false;
+        next = commit.parents.first().cloned();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample_commit() -> Commit {
+        Commit {
+            tree: "0df530b3a655a943b379c67747b940b7199f6116".to_string(),
+            parents: vec![],
+            author: "Jane Doe <jane@example.com> 1700000000 +0000".to_string(),
+            committer: "Jane Doe <jane@example.com> 1700000000 +0000".to_string(),
+            message: "initial commit\n\nwith a body\n".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_format_commit() {
+        let commit = sample_commit();
+        let expected_output = "commit ab3e94d6b7ec38af9d3dee5b424a13a292742c4c\n\
+            Author: Jane Doe <jane@example.com>\n\
+            Date:   Tue Nov 14 22:13:20 2023 +0000\n\
+            \n    initial commit\n    \n    with a body\n";
+
+        let output = format_commit("ab3e94d6b7ec38af9d3dee5b424a13a292742c4c", &commit);
+        assert_eq!(output, expected_output);
+    }
+
+    #[test]
+    fn test_format_oneline() {
+        let commit = sample_commit();
+        let expected_output = "ab3e94d6b7ec38af9d3dee5b424a13a292742c4c initial commit\n";
+
+        let output = format_oneline("ab3e94d6b7ec38af9d3dee5b424a13a292742c4c", &commit);
+        assert_eq!(output, expected_output);
+    }
+
+    #[test]
+    fn format_raw_lists_headers_and_indented_message() {
+        let commit = Commit { parents: vec!["1".repeat(40)], ..sample_commit() };
+        let expected_output = format!("commit ab3e94d6b7ec38af9d3dee5b424a13a292742c4c\n\
+            tree 0df530b3a655a943b379c67747b940b7199f6116\nparent {}\n\
+            author Jane Doe <jane@example.com> 1700000000 +0000\n\
+            committer Jane Doe <jane@example.com> 1700000000 +0000\n\
+            \n    initial commit\n    \n    with a body\n", "1".repeat(40));
+
+        assert_eq!(format_raw("ab3e94d6b7ec38af9d3dee5b424a13a292742c4c", &commit), expected_output);
+    }
+
+    #[test]
+    fn format_json_emits_one_object_per_line() -> Result<(), serde_json::Error> {
+        let commit = sample_commit();
+
+        let output = format_json("ab3e94d6b7ec38af9d3dee5b424a13a292742c4c", &commit)?;
+        assert_eq!(output.matches('\n').count(), 1);
+        let value: serde_json::Value = serde_json::from_str(&output)?;
+        assert_eq!(value["hash"], "ab3e94d6b7ec38af9d3dee5b424a13a292742c4c");
+        assert_eq!(value["tree"], commit.tree.as_str());
+        assert_eq!(value["parents"], serde_json::json!([]));
+        assert_eq!(value["author"], commit.author.as_str());
+        assert_eq!(value["committer"], commit.committer.as_str());
+        assert_eq!(value["message"], "initial commit\n\nwith a body\n");
+        Ok(())
+    }
+
+    #[test]
+    fn format_oneline_with_empty_message() {
+        let commit = Commit { message: String::new(), ..sample_commit() };
+        let expected_output = "ab3e94d6b7ec38af9d3dee5b424a13a292742c4c \n";
+
+        let output = format_oneline("ab3e94d6b7ec38af9d3dee5b424a13a292742c4c", &commit);
+        assert_eq!(output, expected_output);
+    }
+}
@@ -1,10 +1,39 @@
 #![allow(dead_code)]
 use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
 use anyhow::anyhow;
 use thiserror::Error;
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand};
+use catfile::ObjectFormat;
+use color::ColorChoice;
+use log::PrettyFormat;
 
+mod attributes;
+mod branch;
 mod catfile;
+mod checkout;
+mod clone;
+mod color;
+mod commit;
+mod config;
+mod countobjects;
+mod diff;
+mod fsck;
+mod gc;
+mod grep;
+mod index;
+mod log;
+mod pack;
+mod progress;
+mod protocol;
+mod refs;
+mod repo;
+mod show;
+mod status;
+mod tag;
+mod tree;
 
 #[derive(Error, Debug, PartialEq, Eq)]
 enum Error {
@@ -15,32 +44,272 @@ enum Error {
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
+    #[arg(short, long, global = true)]
+    quiet: bool,
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+}
+
+impl Verbosity {
+    pub fn is_quiet(self) -> bool {
+        self == Verbosity::Quiet
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
-    Init,
+    Init {
+        #[arg(long, value_enum, default_value_t = ObjectFormat::Sha1)]
+        object_format: ObjectFormat,
+        path: Option<String>,
+    },
+    #[command(group(ArgGroup::new("mode").args(["pretty", "show_type", "show_size", "exists", "batch", "batch_check"]).required(true)))]
     CatFile {
         #[arg(short = 'p')]
-        pretty: Option<bool>,
-        hash: String,
+        pretty: bool,
+        #[arg(short = 't')]
+        show_type: bool,
+        #[arg(short = 's')]
+        show_size: bool,
+        #[arg(short = 'e')]
+        exists: bool,
+        #[arg(long)]
+        batch: bool,
+        #[arg(long, conflicts_with = "batch")]
+        batch_check: bool,
+        #[arg(long)]
+        allow_unknown_type: bool,
+        #[arg(required_unless_present_any = ["batch", "batch_check"])]
+        hash: Option<String>,
     },
     HashObject {
+        #[arg(short = 't', default_value = "blob")]
+        object_type: String,
         #[arg(short, long)]
         write: bool,
-        path: String,
+        #[arg(long)]
+        stdin: bool,
+        #[arg(long, conflicts_with = "stdin")]
+        stdin_paths: bool,
+        #[arg(long)]
+        no_filters: bool,
+        #[arg(long, conflicts_with_all = ["no_filters", "stdin_paths"])]
+        path: Option<String>,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(0..=9))]
+        compression: Option<u32>,
+        paths: Vec<String>,
+    },
+    LsTree {
+        #[arg(short = 'r')]
+        recurse: bool,
+        #[arg(short = 't')]
+        show_trees: bool,
+        #[arg(long)]
+        name_only: bool,
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "7")]
+        abbrev: Option<usize>,
+        #[arg(long, conflicts_with = "name_only")]
+        format: Option<String>,
+        hash: String,
+    },
+    WriteTree,
+    Mktree,
+    CommitTree {
+        tree: String,
+        #[arg(short = 'p')]
+        parents: Vec<String>,
+        #[arg(short = 'm')]
+        message: String,
+    },
+    Log {
+        #[arg(long, alias = "format", value_enum, default_value_t = PrettyFormat::Full)]
+        pretty: PrettyFormat,
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "7")]
+        abbrev: Option<usize>,
+        #[arg(long, value_enum, num_args = 0..=1, require_equals = true, default_value_t = ColorChoice::Auto, default_missing_value = "always")]
+        color: ColorChoice,
+        revision: Option<String>,
+    },
+    Branch {
+        #[arg(short = 'd', long, requires = "name")]
+        delete: bool,
+        name: Option<String>,
+    },
+    Tag {
+        #[arg(short = 'a', conflicts_with = "delete")]
+        annotated: bool,
+        #[arg(short = 'm', conflicts_with = "delete")]
+        message: Option<String>,
+        #[arg(short = 'd', long)]
+        delete: bool,
+        name: String,
+    },
+    RevParse {
+        name: String,
+    },
+    UpdateRef {
+        #[arg(short = 'd')]
+        delete: bool,
+        #[arg(short = 'm')]
+        message: Option<String>,
+        ref_name: String,
+        #[arg(required_unless_present = "delete")]
+        new_value: Option<String>,
+        #[arg(conflicts_with = "delete")]
+        old_value: Option<String>,
+    },
+    SymbolicRef {
+        #[arg(long, conflicts_with = "value")]
+        short: bool,
+        name: String,
+        value: Option<String>,
+    },
+    Reflog {
+        #[arg(default_value = "HEAD")]
+        name: String,
+    },
+    Add {
+        paths: Vec<String>,
+    },
+    Rm {
+        #[arg(long)]
+        cached: bool,
+        #[arg(short = 'f', long)]
+        force: bool,
+        #[arg(required = true)]
+        paths: Vec<String>,
+    },
+    LsFiles {
+        #[arg(long, short = 's')]
+        stage: bool,
+    },
+    UnpackObjects,
+    VerifyPack {
+        #[arg(short = 'v')]
+        verbose: bool,
+        pack: String,
+    },
+    PackObjects {
+        base_name: String,
     },
+    Gc {
+        #[arg(long)]
+        prune: bool,
+    },
+    CountObjects {
+        #[arg(short = 'v')]
+        verbose: bool,
+    },
+    ShowRef {
+        #[arg(long)]
+        heads: bool,
+        #[arg(long)]
+        tags: bool,
+        #[arg(long)]
+        head: bool,
+    },
+    Grep {
+        #[arg(short = 'n')]
+        line_numbers: bool,
+        #[arg(short = 'i')]
+        ignore_case: bool,
+        pattern: String,
+        rev: Option<String>,
+    },
+    PackRefs {
+        #[arg(long)]
+        all: bool,
+    },
+    ForEachRef {
+        #[arg(long)]
+        format: Option<String>,
+        pattern: Option<String>,
+    },
+    MergeBase {
+        a: String,
+        b: String,
+    },
+    RevList {
+        #[arg(long)]
+        max_count: Option<usize>,
+        #[arg(long)]
+        count: bool,
+        rev: String,
+    },
+    Status {
+        #[arg(long)]
+        porcelain: bool,
+        #[arg(long, value_enum, num_args = 0..=1, require_equals = true, default_value_t = ColorChoice::Auto, default_missing_value = "always")]
+        color: ColorChoice,
+    },
+    Clone {
+        #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        depth: Option<usize>,
+        url: String,
+        dir: Option<String>,
+    },
+    Checkout {
+        target: String,
+    },
+    Diff {
+        a: String,
+        b: String,
+    },
+    Show {
+        #[arg(long)]
+        stat: bool,
+        rev: Option<String>,
+    },
+    Fsck,
 }
 
 
-fn init() -> anyhow::Result<()> {
-    fs::create_dir(".git")?;
-    fs::create_dir(".git/objects")?;
-    fs::create_dir(".git/refs")?;
-    fs::write(".git/HEAD", "ref: refs/heads/main\n")?;
-    println!("Initialized git directory");
+fn config_content(object_format: ObjectFormat) -> String {
+    let version = match object_format {
+        ObjectFormat::Sha1 => 0,
+        ObjectFormat::Sha256 => 1,
+    };
+    let mut content = format!("[core]\n\trepositoryformatversion = {version}\n\tfilemode = {}\n\tbare = false\n", cfg!(unix));
+    if object_format == ObjectFormat::Sha256 {
+        content.push_str(&format!("[extensions]\n\tobjectformat = {}\n", object_format.as_str()));
+    }
+    content
+}
+
+fn create_repository(root: &Path, object_format: ObjectFormat) -> io::Result<bool> {
+    let git_dir = root.join(".git");
+    let reinitialized = git_dir.is_dir();
+    fs::create_dir_all(root)?;
+    for dir in ["objects", "refs/heads", "refs/tags"] {
+        fs::create_dir_all(git_dir.join(dir))?;
+    }
+    if !git_dir.join("HEAD").exists() {
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n")?;
+    }
+    if !git_dir.join("config").exists() {
+        fs::write(git_dir.join("config"), config_content(object_format))?;
+    }
+    Ok(reinitialized)
+}
+
+fn init(path: Option<&str>, object_format: ObjectFormat, verbosity: Verbosity) -> anyhow::Result<()> {
+    let root = Path::new(path.unwrap_or("."));
+    let reinitialized = create_repository(root, object_format)?;
+    let git_dir = root.canonicalize()?.join(".git");
+    if verbosity.is_quiet() {
+        return Ok(());
+    }
+    if reinitialized {
+        println!("Reinitialized existing Git repository in {}/", git_dir.display());
+    } else {
+        println!("Initialized empty Git repository in {}/", git_dir.display());
+    }
     Ok(())
 }
 
@@ -48,15 +317,110 @@ fn unknown_command(command: &str) -> anyhow::Result<()> {
     Err(anyhow!(Error::UnknownCommand(command.to_string())))
 }
 
+fn run(command: &Commands, verbosity: Verbosity) -> anyhow::Result<()> {
+    match command {
+        Commands::Init{ object_format, path } => init(path.as_deref(), *object_format, verbosity),
+        Commands::CatFile{ batch: true, .. } => catfile::cat_file_batch(io::stdin().lock(), &mut io::stdout().lock(), true),
+        Commands::CatFile{ batch_check: true, .. } => catfile::cat_file_batch(io::stdin().lock(), &mut io::stdout().lock(), false),
+        Commands::CatFile{ hash: None, .. } => unreachable!("clap requires a hash without --batch or --batch-check"),
+        Commands::CatFile{ exists: true, hash: Some(hash), .. } => {
+            if !catfile::object_exists(hash) {
+                process::exit(1);
+            }
+            Ok(())
+        }
+        Commands::CatFile{ show_type: true, allow_unknown_type, hash: Some(hash), .. } => {
+            catfile::object_type_name(hash, *allow_unknown_type).map(|object_type| println!("{object_type}"))
+        }
+        Commands::CatFile{ show_size: true, allow_unknown_type, hash: Some(hash), .. } => {
+            catfile::object_size(hash, *allow_unknown_type).map(|size| println!("{size}"))
+        }
+        Commands::CatFile{ hash: Some(hash), .. } => catfile::cat_file(hash),
+        Commands::HashObject{ object_type, write, stdin, stdin_paths, no_filters, path, compression, paths } => {
+            let options = catfile::HashOptions {
+                object_type,
+                write: *write,
+                filters: !no_filters,
+                path: path.as_deref(),
+                compression: *compression,
+            };
+            catfile::hash_object(paths, *stdin, *stdin_paths, &options)
+        }
+        Commands::LsTree{ recurse, show_trees, name_only, abbrev, format, hash } => tree::ls_tree(hash, *recurse, *show_trees, *name_only, *abbrev, format.as_deref()),
+        Commands::WriteTree => {
+            let hash = if repo::git_dir()?.join("index").exists() {
+                tree::write_tree_from_index(&index::read_index()?)?
+            } else {
+                tree::write_tree(&repo::work_tree()?)?
+            };
+            println!("{}", hex::encode(hash));
+            Ok(())
+        }
+        Commands::Mktree => tree::mktree(io::stdin().lock()).map(|hash| println!("{hash}")),
+        Commands::CommitTree{ tree, parents, message } => commit::commit_tree(tree, parents, message).map(|hash| println!("{hash}")),
+        Commands::Log{ pretty, abbrev, color, revision } => log::log(revision.as_deref(), *pretty, *abbrev, *color),
+        Commands::Branch{ delete, name } => branch::branch(name.as_deref(), *delete, verbosity),
+        Commands::Tag{ annotated, message, delete, name } => tag::tag(name, message.as_deref(), *annotated, *delete, verbosity),
+        Commands::RevParse{ name } => refs::rev_parse(name).map(|hash| println!("{hash}")),
+        // `update-ref -d <ref> [<old>]` passes the expected old value second
+        Commands::UpdateRef{ delete: true, ref_name, new_value, .. } => refs::delete_ref(ref_name, new_value.as_deref()),
+        Commands::UpdateRef{ message, ref_name, new_value: Some(new_value), old_value, .. } => {
+            refs::update_ref_checked(ref_name, new_value, old_value.as_deref(), message.as_deref().unwrap_or(""))
+        }
+        Commands::UpdateRef{ new_value: None, .. } => unreachable!("clap requires a new value without -d"),
+        Commands::SymbolicRef{ short, name, value } => refs::symbolic_ref(name, value.as_deref(), *short),
+        Commands::Reflog{ name } => refs::reflog(name),
+        Commands::Add{ paths } => index::add(paths),
+        Commands::Rm{ cached, force, paths } => index::rm(paths, *cached, *force, verbosity),
+        Commands::LsFiles{ stage } => index::ls_files(*stage),
+        Commands::UnpackObjects => pack::unpack_objects(io::stdin().lock()).map(|count| {
+            if !verbosity.is_quiet() {
+                eprintln!("Unpacking objects: 100% ({count}/{count}), done.");
+            }
+        }),
+        Commands::ShowRef{ heads, tags, head } => {
+            if !refs::show_ref(*heads, *tags, *head)? {
+                process::exit(1);
+            }
+            Ok(())
+        }
+        Commands::Grep{ line_numbers, ignore_case, pattern, rev } => {
+            if !grep::grep(pattern, rev.as_deref(), *line_numbers, *ignore_case)? {
+                process::exit(1);
+            }
+            Ok(())
+        }
+        Commands::PackRefs{ all } => refs::pack_refs(*all),
+        Commands::ForEachRef{ format, pattern } => refs::for_each_ref(pattern.as_deref(), format.as_deref()),
+        Commands::PackObjects{ base_name } => pack::pack_objects(io::stdin().lock(), Path::new(base_name))
+            .map(|checksum| println!("{checksum}")),
+        Commands::VerifyPack{ verbose, pack } => pack::verify_pack(Path::new(pack), *verbose),
+        Commands::Gc{ prune } => gc::gc(*prune, verbosity),
+        Commands::CountObjects{ verbose } => countobjects::count_objects(*verbose),
+        Commands::RevList{ max_count, count, rev } => commit::rev_list(rev, *max_count, *count),
+        Commands::MergeBase{ a, b } => match commit::merge_base(a, b)? {
+            Some(hash) => {
+                println!("{hash}");
+                Ok(())
+            }
+            None => process::exit(1),
+        },
+        Commands::Status{ porcelain, color } => status::status(*porcelain, *color),
+        Commands::Clone{ depth, url, dir } => clone::clone(url, dir.as_deref(), *depth, verbosity),
+        Commands::Checkout{ target } => checkout::checkout(target, verbosity),
+        Commands::Diff{ a, b } => diff::diff_blobs(a, b),
+        Commands::Show{ stat, rev } => show::show(rev.as_deref(), *stat),
+        Commands::Fsck => fsck::fsck(),
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
+    let verbosity = if cli.quiet { Verbosity::Quiet } else { Verbosity::Normal };
 
-    if let Err(err) = match &cli.command {
-        Commands::Init => init(),
-        Commands::CatFile{ hash, .. } => catfile::cat_file(hash),
-        Commands::HashObject{ path, .. } => catfile::hash_object(path)
-    } {
-        println!("{err}");
+    if let Err(err) = run(&cli.command, verbosity) {
+        eprintln!("{err:#}");
+        process::exit(1);
     }
 }
 
@@ -78,5 +442,74 @@ mod test {
         }
         panic!();
     }
-}
 
+    #[test]
+    fn unknown_command_error_message_is_preserved() {
+        let actual_error = unknown_command("unknown-command").unwrap_err();
+
+        assert_eq!(format!("{actual_error:#}"), "unknown command: unknown-command");
+    }
+
+    #[test]
+    fn create_repository_creates_standard_layout() -> io::Result<()> {
+        let root = std::env::temp_dir().join("create_repository_standard_layout");
+        let _ = fs::remove_dir_all(&root);
+
+        let reinitialized = create_repository(&root, ObjectFormat::Sha1)?;
+        assert!(!reinitialized);
+        for dir in ["objects", "refs/heads", "refs/tags"] {
+            assert!(root.join(".git").join(dir).is_dir());
+        }
+        assert_eq!(fs::read_to_string(root.join(".git/HEAD"))?, "ref: refs/heads/main\n");
+        Ok(())
+    }
+
+    #[test]
+    fn create_repository_creates_missing_target_directory() -> io::Result<()> {
+        let root = std::env::temp_dir().join("create_repository_missing_target/nested/repo");
+        let _ = fs::remove_dir_all(std::env::temp_dir().join("create_repository_missing_target"));
+
+        create_repository(&root, ObjectFormat::Sha1)?;
+        assert!(root.join(".git/objects").is_dir());
+        Ok(())
+    }
+
+    #[test]
+    fn create_repository_is_idempotent() -> io::Result<()> {
+        let root = std::env::temp_dir().join("create_repository_idempotent");
+        let _ = fs::remove_dir_all(&root);
+        create_repository(&root, ObjectFormat::Sha1)?;
+        fs::write(root.join(".git/HEAD"), "ref: refs/heads/dev\n")?;
+
+        let reinitialized = create_repository(&root, ObjectFormat::Sha1)?;
+        assert!(reinitialized);
+        assert_eq!(fs::read_to_string(root.join(".git/HEAD"))?, "ref: refs/heads/dev\n");
+        Ok(())
+    }
+
+    #[test]
+    fn config_content_is_not_bare() {
+        let values = config::parse(&config_content(ObjectFormat::Sha1));
+
+        assert_eq!(values.get("core.repositoryformatversion").map(String::as_str), Some("0"));
+        assert_eq!(values.get("core.bare").map(String::as_str), Some("false"));
+    }
+
+    #[test]
+    fn config_content_with_sha256_sets_object_format() {
+        let expected_config = format!(
+            "[core]\n\trepositoryformatversion = 1\n\tfilemode = {}\n\tbare = false\n[extensions]\n\tobjectformat = sha256\n",
+            cfg!(unix)
+        );
+
+        let config = config_content(ObjectFormat::Sha256);
+        assert_eq!(config, expected_config);
+    }
+
+    #[test]
+    fn quiet_flag_is_accepted_after_subcommand() {
+        let cli = Cli::try_parse_from(["git", "init", "-q"]).unwrap();
+
+        assert!(cli.quiet);
+    }
+}
@@ -0,0 +1,922 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use flate2::{Compression, Crc};
+use flate2::bufread::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use thiserror::Error;
+use crate::catfile;
+use crate::catfile::{ObjectFormat, ObjectType};
+use crate::progress::Progress;
+
+const INDEX_SIGNATURE: &[u8; 4] = b"\xfftOc";
+const INDEX_VERSION: u32 = 2;
+const PACK_SIGNATURE: &[u8; 4] = b"PACK";
+const PACK_VERSION: u32 = 2;
+const LARGE_OFFSET_FLAG: u32 = 0x8000_0000;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("malformed pack index")]
+    MalformedIndex,
+    #[error("unsupported pack index version: {0}")]
+    UnsupportedVersion(u32),
+    #[error("malformed pack file")]
+    MalformedPack,
+    #[error("unsupported pack object type: {0}")]
+    UnsupportedObjectType(u8),
+    #[error("malformed delta")]
+    MalformedDelta,
+    #[error("delta base {0} not found in pack")]
+    MissingDeltaBase(String),
+    #[error("pack checksum mismatch")]
+    ChecksumMismatch,
+}
+
+enum PackEntry {
+    Object(ObjectType, Vec<u8>),
+    OfsDelta(u64, Vec<u8>),
+    RefDelta(Vec<u8>, Vec<u8>),
+}
+
+/// A version 2 pack index, kept as its raw bytes and searched in place.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PackIndex {
+    data: Vec<u8>,
+    count: usize,
+    hash_len: usize,
+}
+
+const FANOUT_START: usize = 8;
+const HASHES_START: usize = FANOUT_START + 256 * 4;
+
+impl PackIndex {
+    fn fanout(&self, byte: usize) -> usize {
+        be_u32(&self.data, FANOUT_START + byte * 4).unwrap_or_default() as usize
+    }
+
+    fn hash(&self, position: usize) -> &[u8] {
+        let start = HASHES_START + position * self.hash_len;
+        &self.data[start..start + self.hash_len]
+    }
+
+    fn offset(&self, position: usize) -> Option<u64> {
+        let offsets_start = HASHES_START + self.count * (self.hash_len + 4);
+        let offset = be_u32(&self.data, offsets_start + position * 4).ok()?;
+        if offset & LARGE_OFFSET_FLAG == 0 {
+            return Some(offset as u64);
+        }
+        let large_offsets_start = offsets_start + self.count * 4;
+        let position = large_offsets_start + (offset & !LARGE_OFFSET_FLAG) as usize * 8;
+        Some(u64::from_be_bytes(self.data.get(position..position + 8)?.try_into().unwrap()))
+    }
+
+    pub fn find_offset(&self, hash: &[u8]) -> Option<u64> {
+        let first = *hash.first()? as usize;
+        let (mut low, mut high) = (if first == 0 { 0 } else { self.fanout(first - 1) }, self.fanout(first));
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match self.hash(middle).cmp(hash) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return self.offset(middle),
+            }
+        }
+        None
+    }
+
+    pub fn hashes(&self) -> impl Iterator<Item = String> + '_ {
+        (0..self.count).map(|position| hex::encode(self.hash(position)))
+    }
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    let bytes = data.get(offset..offset + 4).ok_or(Error::MalformedIndex)?;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn parse_pack_index(data: Vec<u8>, format: ObjectFormat) -> Result<PackIndex, Error> {
+    if data.get(..4) != Some(INDEX_SIGNATURE) {
+        return Err(Error::MalformedIndex);
+    }
+    let version = be_u32(&data, 4)?;
+    if version != INDEX_VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+    let mut previous = 0;
+    for byte in 0..256 {
+        let count = be_u32(&data, FANOUT_START + byte * 4)?;
+        if count < previous {
+            return Err(Error::MalformedIndex);
+        }
+        previous = count;
+    }
+    let index = PackIndex { count: previous as usize, hash_len: format.raw_len(), data };
+    let offsets_end = HASHES_START + index.count * (index.hash_len + 8);
+    if index.data.len() < offsets_end || (0..index.count).any(|position| index.offset(position).is_none()) {
+        return Err(Error::MalformedIndex);
+    }
+    Ok(index)
+}
+
+pub(crate) fn pack_object_count(data: &[u8]) -> Result<u32, Error> {
+    if data.get(..4) != Some(INDEX_SIGNATURE) {
+        return Err(Error::MalformedIndex);
+    }
+    let version = be_u32(data, 4)?;
+    if version != INDEX_VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+    be_u32(data, 8 + 255 * 4)
+}
+
+fn read_byte<R: Read>(reader: &mut R) -> Result<u8, Error> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte).map_err(|_|Error::MalformedPack)?;
+    Ok(byte[0])
+}
+
+fn read_object_header<R: Read>(reader: &mut R) -> Result<(u8, usize), Error> {
+    let mut byte = read_byte(reader)?;
+    let kind = (byte >> 4) & 0x7;
+    let mut size = (byte & 0x0f) as usize;
+    let mut shift = 4;
+    while byte & 0x80 != 0 {
+        byte = read_byte(reader)?;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+    }
+    Ok((kind, size))
+}
+
+fn object_type_of(kind: u8) -> Result<ObjectType, Error> {
+    match kind {
+        1 => Ok(ObjectType::Commit),
+        2 => Ok(ObjectType::Tree),
+        3 => Ok(ObjectType::Blob),
+        4 => Ok(ObjectType::Tag),
+        kind => Err(Error::UnsupportedObjectType(kind)),
+    }
+}
+
+fn type_code(object_type: ObjectType) -> u8 {
+    match object_type {
+        ObjectType::Commit => 1,
+        ObjectType::Tree => 2,
+        ObjectType::Blob => 3,
+        ObjectType::Tag => 4,
+    }
+}
+
+fn encode_object_header(kind: u8, size: usize) -> Vec<u8> {
+    let mut header = vec![(kind << 4) | (size & 0x0f) as u8];
+    let mut size = size >> 4;
+    while size > 0 {
+        *header.last_mut().unwrap() |= 0x80;
+        header.push((size & 0x7f) as u8);
+        size >>= 7;
+    }
+    header
+}
+
+fn inflate<R: BufRead>(reader: &mut R, size: usize) -> Result<Vec<u8>, Error> {
+    let mut content = Vec::with_capacity(size);
+    ZlibDecoder::new(reader).read_to_end(&mut content).map_err(|_|Error::MalformedPack)?;
+    if content.len() != size {
+        return Err(Error::MalformedPack);
+    }
+    Ok(content)
+}
+
+fn read_base_offset<R: Read>(reader: &mut R) -> Result<u64, Error> {
+    let mut byte = read_byte(reader)?;
+    let mut offset = (byte & 0x7f) as u64;
+    while byte & 0x80 != 0 {
+        byte = read_byte(reader)?;
+        offset = ((offset + 1) << 7) | (byte & 0x7f) as u64;
+    }
+    Ok(offset)
+}
+
+fn read_entry<R: Read + Seek>(pack: &mut R, offset: u64, hash_len: usize) -> Result<(PackEntry, u64), Error> {
+    pack.seek(SeekFrom::Start(offset)).map_err(|_|Error::MalformedPack)?;
+    let mut pack = io::BufReader::new(pack);
+    let (kind, size) = read_object_header(&mut pack)?;
+    let entry = match kind {
+        6 => {
+            let base_offset = offset.checked_sub(read_base_offset(&mut pack)?).ok_or(Error::MalformedPack)?;
+            PackEntry::OfsDelta(base_offset, inflate(&mut pack, size)?)
+        }
+        7 => {
+            let mut base_hash = vec![0; hash_len];
+            pack.read_exact(&mut base_hash).map_err(|_|Error::MalformedPack)?;
+            PackEntry::RefDelta(base_hash, inflate(&mut pack, size)?)
+        }
+        kind => PackEntry::Object(object_type_of(kind)?, inflate(&mut pack, size)?),
+    };
+    let end = pack.stream_position().map_err(|_|Error::MalformedPack)?;
+    Ok((entry, end))
+}
+
+fn read_delta_size(delta: &[u8], position: &mut usize) -> Result<usize, Error> {
+    let mut size = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *delta.get(*position).ok_or(Error::MalformedDelta)?;
+        *position += 1;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut position = 0;
+    if read_delta_size(delta, &mut position)? != base.len() {
+        return Err(Error::MalformedDelta);
+    }
+    let target_size = read_delta_size(delta, &mut position)?;
+    let mut target = Vec::with_capacity(target_size);
+    while let Some(&instruction) = delta.get(position) {
+        position += 1;
+        if instruction & 0x80 != 0 {
+            let mut fields = [0usize; 7];
+            for (bit, field) in fields.iter_mut().enumerate() {
+                if instruction & (1 << bit) != 0 {
+                    *field = *delta.get(position).ok_or(Error::MalformedDelta)? as usize;
+                    position += 1;
+                }
+            }
+            let offset = fields[0] | fields[1] << 8 | fields[2] << 16 | fields[3] << 24;
+            let size = match fields[4] | fields[5] << 8 | fields[6] << 16 {
+                0 => 0x10000,
+                size => size,
+            };
+            target.extend_from_slice(base.get(offset..offset + size).ok_or(Error::MalformedDelta)?);
+        } else if instruction != 0 {
+            let size = instruction as usize;
+            target.extend_from_slice(delta.get(position..position + size).ok_or(Error::MalformedDelta)?);
+            position += size;
+        } else {
+            return Err(Error::MalformedDelta);
+        }
+    }
+    if target.len() != target_size {
+        return Err(Error::MalformedDelta);
+    }
+    Ok(target)
+}
+
+fn read_pack_object<R: Read + Seek>(pack: &mut R, index: &PackIndex, offset: u64, format: ObjectFormat) -> Result<(ObjectType, Vec<u8>), Error> {
+    let mut signature = [0; 4];
+    pack.seek(SeekFrom::Start(0)).map_err(|_|Error::MalformedPack)?;
+    pack.read_exact(&mut signature).map_err(|_|Error::MalformedPack)?;
+    if &signature != PACK_SIGNATURE {
+        return Err(Error::MalformedPack);
+    }
+    let mut deltas = Vec::new();
+    let mut offset = offset;
+    let (object_type, mut content) = loop {
+        match read_entry(pack, offset, format.raw_len())?.0 {
+            PackEntry::Object(object_type, content) => break (object_type, content),
+            PackEntry::OfsDelta(base_offset, delta) => {
+                deltas.push(delta);
+                offset = base_offset;
+            }
+            PackEntry::RefDelta(base_hash, delta) => {
+                deltas.push(delta);
+                offset = index.find_offset(&base_hash).ok_or_else(|| Error::MissingDeltaBase(hex::encode(&base_hash)))?;
+            }
+        }
+    };
+    for delta in deltas.iter().rev() {
+        content = apply_delta(&content, delta)?;
+    }
+    Ok((object_type, content))
+}
+
+fn check_pack_header(data: &[u8]) -> Result<u32, Error> {
+    if data.get(..4) != Some(PACK_SIGNATURE) || data.len() < 12 {
+        return Err(Error::MalformedPack);
+    }
+    let version = u32::from_be_bytes(data[4..8].try_into().unwrap());
+    if !(2..=3).contains(&version) {
+        return Err(Error::MalformedPack);
+    }
+    Ok(u32::from_be_bytes(data[8..12].try_into().unwrap()))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct PackedObject {
+    pub hash: Vec<u8>,
+    pub object_type: ObjectType,
+    pub content: Vec<u8>,
+    offset: u64,
+    crc: u32,
+}
+
+pub fn parse_pack(data: &[u8], format: ObjectFormat) -> Result<Vec<PackedObject>, Error> {
+    parse_pack_with_progress(data, format, false)
+}
+
+fn parse_pack_with_progress(data: &[u8], format: ObjectFormat, show_progress: bool) -> Result<Vec<PackedObject>, Error> {
+    let count = check_pack_header(data)?;
+    let hash_len = format.raw_len();
+    let mut pack = io::Cursor::new(data);
+    let mut offset = 12;
+    let mut entries = Vec::new();
+    let mut progress = Progress::new("Receiving objects", count, show_progress);
+    for done in 0..count {
+        progress.update(done);
+        let (entry, end) = read_entry(&mut pack, offset, hash_len)?;
+        let mut crc = Crc::new();
+        crc.update(&data[offset as usize..end as usize]);
+        entries.push((offset, crc.sum(), entry));
+        offset = end;
+    }
+    progress.finish();
+    let (content, checksum) = data.split_at(offset as usize);
+    if format.digest(content) != checksum {
+        return Err(Error::MalformedPack);
+    }
+
+    let mut resolved: HashMap<u64, (ObjectType, Vec<u8>)> = HashMap::new();
+    let mut offsets_by_hash: HashMap<Vec<u8>, u64> = HashMap::new();
+    let mut objects = Vec::new();
+    while objects.len() < entries.len() {
+        let resolved_before = objects.len();
+        for (offset, crc, entry) in &entries {
+            if resolved.contains_key(offset) {
+                continue;
+            }
+            let object = match entry {
+                PackEntry::Object(object_type, content) => Some((*object_type, content.clone())),
+                PackEntry::OfsDelta(base_offset, delta) => resolved.get(base_offset)
+                    .map(|(object_type, base)| Ok::<_, Error>((*object_type, apply_delta(base, delta)?)))
+                    .transpose()?,
+                PackEntry::RefDelta(base_hash, delta) => offsets_by_hash.get(base_hash)
+                    .and_then(|base_offset| resolved.get(base_offset))
+                    .map(|(object_type, base)| Ok::<_, Error>((*object_type, apply_delta(base, delta)?)))
+                    .transpose()?,
+            };
+            let Some((object_type, content)) = object else {
+                continue;
+            };
+            let hash = format.digest(&catfile::object_payload(object_type, &content));
+            offsets_by_hash.insert(hash.clone(), *offset);
+            resolved.insert(*offset, (object_type, content.clone()));
+            objects.push(PackedObject { hash, object_type, content, offset: *offset, crc: *crc });
+        }
+        if objects.len() == resolved_before {
+            let missing = entries.iter().find_map(|(offset, _, entry)| match entry {
+                PackEntry::RefDelta(base_hash, _) if !resolved.contains_key(offset) => Some(hex::encode(base_hash)),
+                _ => None,
+            });
+            return Err(Error::MissingDeltaBase(missing.unwrap_or_default()));
+        }
+    }
+    objects.sort_by_key(|object| object.offset);
+    Ok(objects)
+}
+
+pub fn write_pack_index(objects: &[PackedObject], pack_checksum: &[u8], format: ObjectFormat) -> Vec<u8> {
+    let mut objects: Vec<&PackedObject> = objects.iter().collect();
+    objects.sort_by(|a, b| a.hash.cmp(&b.hash));
+    let mut index = INDEX_SIGNATURE.to_vec();
+    index.extend_from_slice(&INDEX_VERSION.to_be_bytes());
+    for first_byte in 0..=255u8 {
+        let count = objects.iter().filter(|object| object.hash[0] <= first_byte).count() as u32;
+        index.extend_from_slice(&count.to_be_bytes());
+    }
+    objects.iter().for_each(|object| index.extend_from_slice(&object.hash));
+    objects.iter().for_each(|object| index.extend_from_slice(&object.crc.to_be_bytes()));
+    let mut large_offsets = Vec::new();
+    for object in &objects {
+        if object.offset < LARGE_OFFSET_FLAG as u64 {
+            index.extend_from_slice(&(object.offset as u32).to_be_bytes());
+        } else {
+            index.extend_from_slice(&(LARGE_OFFSET_FLAG | large_offsets.len() as u32).to_be_bytes());
+            large_offsets.push(object.offset);
+        }
+    }
+    large_offsets.iter().for_each(|offset| index.extend_from_slice(&offset.to_be_bytes()));
+    index.extend_from_slice(pack_checksum);
+    let checksum = format.digest(&index);
+    index.extend(checksum);
+    index
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct VerifiedEntry {
+    hash: Vec<u8>,
+    object_type: ObjectType,
+    size: usize,
+    packed_size: u64,
+    offset: u64,
+    depth: usize,
+    base: Option<Vec<u8>>,
+}
+
+fn verify_pack_data(data: &[u8], format: ObjectFormat) -> Result<Vec<VerifiedEntry>, Error> {
+    check_pack_header(data)?;
+    let hash_len = format.raw_len();
+    if data.len() < 12 + hash_len {
+        return Err(Error::MalformedPack);
+    }
+    let (content, checksum) = data.split_at(data.len() - hash_len);
+    if format.digest(content) != checksum {
+        return Err(Error::ChecksumMismatch);
+    }
+    let objects = parse_pack(data, format)?;
+    let offsets_by_hash: HashMap<&[u8], u64> = objects.iter().map(|object| (object.hash.as_slice(), object.offset)).collect();
+    let hashes_by_offset: HashMap<u64, &[u8]> = objects.iter().map(|object| (object.offset, object.hash.as_slice())).collect();
+
+    let mut pack = io::Cursor::new(data);
+    let mut base_offsets: HashMap<u64, u64> = HashMap::new();
+    let mut entries = Vec::new();
+    for object in &objects {
+        let (entry, end) = read_entry(&mut pack, object.offset, hash_len)?;
+        let base_offset = match &entry {
+            PackEntry::Object(..) => None,
+            PackEntry::OfsDelta(base_offset, _) => Some(*base_offset),
+            PackEntry::RefDelta(base_hash, _) => offsets_by_hash.get(base_hash.as_slice()).copied(),
+        };
+        if let Some(base_offset) = base_offset {
+            base_offsets.insert(object.offset, base_offset);
+        }
+        entries.push((object, object.content.len(), end - object.offset, base_offset));
+    }
+
+    Ok(entries
+        .into_iter()
+        .map(|(object, size, packed_size, base_offset)| {
+            let mut depth = 0;
+            let mut current = object.offset;
+            while let Some(base) = base_offsets.get(&current) {
+                depth += 1;
+                current = *base;
+            }
+            VerifiedEntry {
+                hash: object.hash.clone(),
+                object_type: object.object_type,
+                size,
+                packed_size,
+                offset: object.offset,
+                depth,
+                base: base_offset.and_then(|offset| hashes_by_offset.get(&offset)).map(|hash| hash.to_vec()),
+            }
+        })
+        .collect())
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 { "object" } else { "objects" }
+}
+
+fn verify_report(entries: &[VerifiedEntry]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut chains: BTreeMap<usize, usize> = BTreeMap::new();
+    for entry in entries {
+        let mut line = format!(
+            "{} {:<6} {} {} {}",
+            hex::encode(&entry.hash), entry.object_type.as_str(), entry.size, entry.packed_size, entry.offset,
+        );
+        if let Some(base) = &entry.base {
+            line.push_str(&format!(" {} {}", entry.depth, hex::encode(base)));
+        }
+        lines.push(line);
+        *chains.entry(entry.depth).or_default() += 1;
+    }
+    for (depth, count) in chains {
+        match depth {
+            0 => lines.push(format!("non delta: {count} {}", plural(count))),
+            depth => lines.push(format!("chain length = {depth}: {count} {}", plural(count))),
+        }
+    }
+    lines
+}
+
+pub fn verify_pack(path: &Path, verbose: bool) -> anyhow::Result<()> {
+    let pack_path = path.with_extension("pack");
+    let entries = verify_pack_data(&fs::read(&pack_path)?, ObjectFormat::current())?;
+    if verbose {
+        for line in verify_report(&entries) {
+            println!("{line}");
+        }
+        println!("{}: ok", pack_path.display());
+    }
+    Ok(())
+}
+
+pub fn write_pack(objects: &[(ObjectType, Vec<u8>)], format: ObjectFormat) -> io::Result<(Vec<u8>, Vec<PackedObject>)> {
+    let mut data = PACK_SIGNATURE.to_vec();
+    data.extend_from_slice(&PACK_VERSION.to_be_bytes());
+    data.extend_from_slice(&(objects.len() as u32).to_be_bytes());
+    let mut packed = Vec::new();
+    for (object_type, content) in objects {
+        let offset = data.len();
+        data.extend(encode_object_header(type_code(*object_type), content.len()));
+        let mut encoder = ZlibEncoder::new(&mut data, Compression::default());
+        encoder.write_all(content)?;
+        encoder.finish()?;
+        let mut crc = Crc::new();
+        crc.update(&data[offset..]);
+        packed.push(PackedObject {
+            hash: format.digest(&catfile::object_payload(*object_type, content)),
+            object_type: *object_type,
+            content: content.clone(),
+            offset: offset as u64,
+            crc: crc.sum(),
+        });
+    }
+    let checksum = format.digest(&data);
+    data.extend(checksum);
+    Ok((data, packed))
+}
+
+pub fn write_pack_files(base: &Path, data: &[u8], objects: &[PackedObject], format: ObjectFormat) -> io::Result<String> {
+    let pack_checksum = &data[data.len() - format.raw_len()..];
+    let name = format!("{}-{}", base.display(), hex::encode(pack_checksum));
+    fs::write(format!("{name}.pack"), data)?;
+    fs::write(format!("{name}.idx"), write_pack_index(objects, pack_checksum, format))?;
+    Ok(hex::encode(pack_checksum))
+}
+
+pub fn store_pack(objects_dir: &Path, data: &[u8], format: ObjectFormat, show_progress: bool) -> anyhow::Result<Vec<PackedObject>> {
+    let objects = parse_pack_with_progress(data, format, show_progress)?;
+    let pack_dir = objects_dir.join("pack");
+    fs::create_dir_all(&pack_dir)?;
+    write_pack_files(&pack_dir.join("pack"), data, &objects, format)?;
+    Ok(objects)
+}
+
+pub fn pack_objects<R: BufRead>(input: R, base: &Path) -> anyhow::Result<String> {
+    let mut objects = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        if let Some(hash) = line.split_whitespace().next() {
+            objects.push(catfile::read_object(&catfile::resolve_hash(hash)?, catfile::ParseMode::Strict)?);
+        }
+    }
+    let format = ObjectFormat::current();
+    let (data, packed) = write_pack(&objects, format)?;
+    Ok(write_pack_files(base, &data, &packed, format)?)
+}
+
+pub fn unpack_objects<R: Read>(mut input: R) -> anyhow::Result<usize> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    let objects = parse_pack(&data, ObjectFormat::current())?;
+    for object in &objects {
+        catfile::write_object_content(object.object_type, &object.content)?;
+    }
+    Ok(objects.len())
+}
+
+pub(crate) fn pack_indexes(objects_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(objects_dir.join("pack")) else {
+        return Vec::new();
+    };
+    let mut indexes: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "idx"))
+        .collect();
+    indexes.sort();
+    indexes
+}
+
+fn read_pack_index(path: &Path, format: ObjectFormat) -> anyhow::Result<Arc<PackIndex>> {
+    static CACHE: Mutex<BTreeMap<PathBuf, (fs::Metadata, Arc<PackIndex>)>> = Mutex::new(BTreeMap::new());
+    let metadata = fs::metadata(path)?;
+    let unchanged = |cached: &fs::Metadata| cached.len() == metadata.len() && cached.modified().ok() == metadata.modified().ok();
+    let mut cache = CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some((_, index)) = cache.get(path).filter(|(cached, _)| unchanged(cached)) {
+        return Ok(Arc::clone(index));
+    }
+    let index = Arc::new(parse_pack_index(fs::read(path)?, format)?);
+    cache.insert(path.to_path_buf(), (metadata, Arc::clone(&index)));
+    Ok(index)
+}
+
+pub fn find_packed_object(objects_dir: &Path, hash: &str, format: ObjectFormat) -> anyhow::Result<Option<(ObjectType, Vec<u8>)>> {
+    let raw_hash = hex::decode(hash)?;
+    for index_path in pack_indexes(objects_dir) {
+        let index = read_pack_index(&index_path, format)?;
+        let Some(offset) = index.find_offset(&raw_hash) else {
+            continue;
+        };
+        let mut pack = fs::File::open(index_path.with_extension("pack"))?;
+        return Ok(Some(read_pack_object(&mut pack, &index, offset, format)?));
+    }
+    Ok(None)
+}
+
+pub fn packed_hashes(objects_dir: &Path, format: ObjectFormat) -> Vec<String> {
+    pack_indexes(objects_dir)
+        .iter()
+        .filter_map(|path| read_pack_index(path, format).ok())
+        .flat_map(|index| index.hashes().collect::<Vec<_>>())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+
+    fn deflate(content: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn sample_pack(objects: &[(u8, Vec<u8>)]) -> (Vec<u8>, Vec<u64>) {
+        let mut pack = PACK_SIGNATURE.to_vec();
+        pack.extend_from_slice(&2u32.to_be_bytes());
+        pack.extend_from_slice(&(objects.len() as u32).to_be_bytes());
+        let mut offsets = Vec::new();
+        for (kind, content) in objects {
+            offsets.push(pack.len() as u64);
+            pack.extend(encode_object_header(*kind, content.len()));
+            pack.extend(deflate(content));
+        }
+        (pack, offsets)
+    }
+
+    fn sample_index(entries: &[(Vec<u8>, u32)]) -> Vec<u8> {
+        let mut entries = entries.to_vec();
+        entries.sort();
+        let mut index = INDEX_SIGNATURE.to_vec();
+        index.extend_from_slice(&INDEX_VERSION.to_be_bytes());
+        for first_byte in 0..=255u8 {
+            let count = entries.iter().filter(|(hash, _)| hash[0] <= first_byte).count() as u32;
+            index.extend_from_slice(&count.to_be_bytes());
+        }
+        entries.iter().for_each(|(hash, _)| index.extend_from_slice(hash));
+        entries.iter().for_each(|_| index.extend_from_slice(&0u32.to_be_bytes()));
+        entries.iter().for_each(|(_, offset)| index.extend_from_slice(&offset.to_be_bytes()));
+        index
+    }
+
+    #[test]
+    fn parse_pack_index_finds_offsets() -> Result<(), Error> {
+        let first = vec![0x12; 20];
+        let second = vec![0xab; 20];
+        let index = sample_index(&[(second.clone(), 200), (first.clone(), 12)]);
+
+        let index = parse_pack_index(index, ObjectFormat::Sha1)?;
+
+        assert_eq!(index.find_offset(&first), Some(12));
+        assert_eq!(index.find_offset(&second), Some(200));
+        assert_eq!(index.find_offset(&[0x34; 20]), None);
+        Ok(())
+    }
+
+    #[test]
+    fn find_offset_searches_within_fanout_bucket() -> Result<(), Error> {
+        let hash = |first: u8, rest: u8| [vec![first], vec![rest; 19]].concat();
+        let entries: Vec<(Vec<u8>, u32)> = [(0x00, 1), (0x00, 9), (0x7f, 3), (0x7f, 4), (0x7f, 5), (0xff, 0)]
+            .iter()
+            .enumerate()
+            .map(|(position, &(first, rest))| (hash(first, rest), position as u32 * 10))
+            .collect();
+
+        let index = parse_pack_index(sample_index(&entries), ObjectFormat::Sha1)?;
+
+        for (hash, offset) in &entries {
+            assert_eq!(index.find_offset(hash), Some(*offset as u64));
+        }
+        assert_eq!(index.find_offset(&hash(0x7f, 6)), None);
+        assert_eq!(index.find_offset(&hash(0x80, 3)), None);
+        assert_eq!(index.hashes().count(), entries.len());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_pack_index_is_error_with_decreasing_fanout() {
+        let mut index = sample_index(&[(vec![0x12; 20], 12)]);
+        index[8 + 0x20 * 4..8 + 0x21 * 4].copy_from_slice(&0u32.to_be_bytes());
+
+        assert_eq!(parse_pack_index(index, ObjectFormat::Sha1), Err(Error::MalformedIndex));
+    }
+
+    #[test]
+    fn parse_pack_index_is_error_with_unsupported_version() {
+        let mut index = sample_index(&[]);
+        index[4..8].copy_from_slice(&1u32.to_be_bytes());
+
+        let actual_result = parse_pack_index(index, ObjectFormat::Sha1);
+
+        assert_eq!(actual_result, Err(Error::UnsupportedVersion(1)));
+    }
+
+    #[test]
+    fn read_object_header_decodes_size_varint() -> Result<(), Error> {
+        let header = encode_object_header(3, 300);
+
+        assert_eq!(read_object_header(&mut header.as_slice())?, (3, 300));
+        Ok(())
+    }
+
+    #[test]
+    fn read_pack_object_reads_non_delta_objects() -> Result<(), Error> {
+        let content = "what is up, doc?".repeat(30);
+        let (pack, offsets) = sample_pack(&[(1, b"tree 1234\n".to_vec()), (3, content.clone().into_bytes())]);
+        let index = parse_pack_index(sample_index(&[]), ObjectFormat::Sha1)?;
+
+        let object = read_pack_object(&mut Cursor::new(pack), &index, offsets[1], ObjectFormat::Sha1)?;
+
+        assert_eq!(object, (ObjectType::Blob, content.into_bytes()));
+        Ok(())
+    }
+
+    #[test]
+    fn apply_delta_copies_and_inserts() -> Result<(), Error> {
+        let base = b"hello world";
+        let delta = [11, 13, 0x91, 0, 6, 7, b'k', b'i', b't', b't', b'y', b'!', b'!'];
+
+        assert_eq!(apply_delta(base, &delta)?, b"hello kitty!!");
+        Ok(())
+    }
+
+    #[test]
+    fn apply_delta_is_error_with_wrong_base_size() {
+        let delta = [3, 1, 1, b'x'];
+
+        assert_eq!(apply_delta(b"hello", &delta), Err(Error::MalformedDelta));
+    }
+
+    #[test]
+    fn read_pack_object_resolves_delta_chain() -> Result<(), Error> {
+        let greeting_hash = vec![0x42; 20];
+        let ofs_delta = [11, 13, 0x91, 0, 6, 7, b'k', b'i', b't', b't', b'y', b'!', b'!'];
+        let ref_delta = [13, 11, 0x90, 6, 5, b'b', b'y', b'e', b'!', b'!'];
+        let (mut pack, mut offsets) = sample_pack(&[(3, b"hello world".to_vec())]);
+        offsets.push(pack.len() as u64);
+        pack.extend(encode_object_header(6, ofs_delta.len()));
+        pack.push((offsets[1] - offsets[0]) as u8);
+        pack.extend(deflate(&ofs_delta));
+        offsets.push(pack.len() as u64);
+        pack.extend(encode_object_header(7, ref_delta.len()));
+        pack.extend_from_slice(&greeting_hash);
+        pack.extend(deflate(&ref_delta));
+        let index = parse_pack_index(sample_index(&[(greeting_hash, offsets[1] as u32)]), ObjectFormat::Sha1)?;
+
+        let object = read_pack_object(&mut Cursor::new(pack), &index, offsets[2], ObjectFormat::Sha1)?;
+
+        assert_eq!(object, (ObjectType::Blob, b"hello bye!!".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn read_pack_object_is_error_with_missing_ref_delta_base() -> Result<(), Error> {
+        let (mut pack, _) = sample_pack(&[]);
+        let offset = pack.len() as u64;
+        pack.extend(encode_object_header(7, 4));
+        pack.extend_from_slice(&[0x42; 20]);
+        pack.extend(deflate(&[1, 1, 1, b'x']));
+        let index = parse_pack_index(sample_index(&[]), ObjectFormat::Sha1)?;
+
+        let actual_result = read_pack_object(&mut Cursor::new(pack), &index, offset, ObjectFormat::Sha1);
+
+        assert_eq!(actual_result, Err(Error::MissingDeltaBase("42".repeat(20))));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_pack_resolves_deltas_and_hashes_objects() -> Result<(), Error> {
+        let ofs_delta = [11, 13, 0x91, 0, 6, 7, b'k', b'i', b't', b't', b'y', b'!', b'!'];
+        let (mut pack, offsets) = sample_pack(&[(3, b"hello world".to_vec())]);
+        let delta_offset = pack.len() as u64;
+        pack.extend(encode_object_header(6, ofs_delta.len()));
+        pack.push((delta_offset - offsets[0]) as u8);
+        pack.extend(deflate(&ofs_delta));
+        pack[8..12].copy_from_slice(&2u32.to_be_bytes());
+        let checksum = ObjectFormat::Sha1.digest(&pack);
+        pack.extend(checksum);
+
+        let objects = parse_pack(&pack, ObjectFormat::Sha1)?;
+
+        let hashes: Vec<String> = objects.iter().map(|object| hex::encode(&object.hash)).collect();
+        assert_eq!(hashes, vec!["95d09f2b10159347eece71399a7e2e907ea3df4f", "2831f75ea6f0701cac2517cc50a36331ba6deee9"]);
+        assert_eq!(objects[1].content, b"hello kitty!!");
+        Ok(())
+    }
+
+    #[test]
+    fn verify_report_lists_objects_and_delta_chains() -> Result<(), Error> {
+        let ofs_delta = [11, 17, 0x90, 11, 6, b',', b' ', b'b', b'y', b'e', b'!'];
+        let (mut pack, offsets) = sample_pack(&[(3, b"hello world".to_vec())]);
+        let delta_offset = pack.len() as u64;
+        pack.extend(encode_object_header(6, ofs_delta.len()));
+        pack.push((delta_offset - offsets[0]) as u8);
+        pack.extend(deflate(&ofs_delta));
+        pack[8..12].copy_from_slice(&2u32.to_be_bytes());
+        let checksum = ObjectFormat::Sha1.digest(&pack);
+        pack.extend(checksum);
+
+        let lines = verify_report(&verify_pack_data(&pack, ObjectFormat::Sha1)?);
+        assert_eq!(lines, [
+            format!("95d09f2b10159347eece71399a7e2e907ea3df4f blob   11 {} 12", delta_offset - 12),
+            format!("19bdc68c00cc64dbaf00190d42e43cd7ecf26996 blob   17 {} {delta_offset} 1 95d09f2b10159347eece71399a7e2e907ea3df4f", pack.len() as u64 - 20 - delta_offset),
+            "non delta: 1 object".to_string(),
+            "chain length = 1: 1 object".to_string(),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn verify_pack_data_is_error_with_bad_checksum() {
+        let (mut pack, _) = sample_pack(&[(3, b"hello world".to_vec())]);
+        pack.extend([0; 20]);
+
+        assert_eq!(verify_pack_data(&pack, ObjectFormat::Sha1), Err(Error::ChecksumMismatch));
+    }
+
+    #[test]
+    fn parse_pack_is_error_with_bad_checksum() {
+        let (mut pack, _) = sample_pack(&[(3, b"hello world".to_vec())]);
+        pack.extend([0; 20]);
+
+        assert_eq!(parse_pack(&pack, ObjectFormat::Sha1), Err(Error::MalformedPack));
+    }
+
+    #[test]
+    fn write_pack_index_round_trips_through_parse_pack_index() -> Result<(), Error> {
+        let (mut pack, _) = sample_pack(&[(3, b"hello world".to_vec()), (3, b"what is up, doc?".to_vec())]);
+        let checksum = ObjectFormat::Sha1.digest(&pack);
+        pack.extend(checksum.clone());
+        let objects = parse_pack(&pack, ObjectFormat::Sha1)?;
+
+        let index = parse_pack_index(write_pack_index(&objects, &checksum, ObjectFormat::Sha1), ObjectFormat::Sha1)?;
+
+        for object in &objects {
+            assert_eq!(index.find_offset(&object.hash), Some(object.offset));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn write_pack_round_trips_through_parse_pack() -> Result<(), Error> {
+        let objects = vec![
+            (ObjectType::Blob, b"hello world".to_vec()),
+            (ObjectType::Tree, Vec::new()),
+            (ObjectType::Blob, vec![b'x'; 300]),
+        ];
+
+        let (data, written) = write_pack(&objects, ObjectFormat::Sha1).unwrap();
+        let parsed = parse_pack(&data, ObjectFormat::Sha1)?;
+        assert_eq!(parsed, written);
+        assert_eq!(hex::encode(&parsed[1].hash), "4b825dc642cb6eb9a060e54bf8d69288fbee4904");
+
+        let index = parse_pack_index(write_pack_index(&written, &data[data.len() - 20..], ObjectFormat::Sha1), ObjectFormat::Sha1)?;
+        let entry = read_pack_object(&mut io::Cursor::new(&data), &index, written[2].offset, ObjectFormat::Sha1)?;
+        assert_eq!(entry, (ObjectType::Blob, vec![b'x'; 300]));
+        Ok(())
+    }
+
+    #[test]
+    fn unpack_objects_writes_loose_objects() -> anyhow::Result<()> {
+        let _lock = crate::repo::ENV_LOCK.lock().unwrap();
+        let git_dir = std::env::temp_dir().join("unpack_objects_git_dir");
+        let _ = fs::remove_dir_all(&git_dir);
+        fs::create_dir_all(git_dir.join("objects"))?;
+        let (mut pack, _) = sample_pack(&[(3, b"hello world".to_vec()), (3, b"what is up, doc?".to_vec())]);
+        let checksum = ObjectFormat::Sha1.digest(&pack);
+        pack.extend(checksum);
+
+        std::env::set_var("GIT_DIR", &git_dir);
+        let result = unpack_objects(pack.as_slice());
+        std::env::remove_var("GIT_DIR");
+
+        assert_eq!(result?, 2);
+        for hash in ["95d09f2b10159347eece71399a7e2e907ea3df4f", "bd9dbf5aae1a3862dd1526723246b20206e5fc37"] {
+            assert!(git_dir.join("objects").join(&hash[..2]).join(&hash[2..]).is_file());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn find_packed_object_reads_from_pack_directory() -> anyhow::Result<()> {
+        let objects_dir = std::env::temp_dir().join("find_packed_object_objects");
+        let pack_dir = objects_dir.join("pack");
+        fs::create_dir_all(&pack_dir)?;
+        let hash = "bd9dbf5aae1a3862dd1526723246b20206e5fc37";
+        let (pack, offsets) = sample_pack(&[(3, b"what is up, doc?".to_vec())]);
+        fs::write(pack_dir.join("pack-test.pack"), pack)?;
+        fs::write(pack_dir.join("pack-test.idx"), sample_index(&[(hex::decode(hash)?, offsets[0] as u32)]))?;
+
+        let object = find_packed_object(&objects_dir, hash, ObjectFormat::Sha1)?;
+
+        assert_eq!(object, Some((ObjectType::Blob, b"what is up, doc?".to_vec())));
+        assert_eq!(packed_hashes(&objects_dir, ObjectFormat::Sha1), vec![hash.to_string()]);
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct Progress {
+    title: &'static str,
+    total: u32,
+    enabled: bool,
+    last_update: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new(title: &'static str, total: u32, enabled: bool) -> Self {
+        Progress { title, total, enabled, last_update: None }
+    }
+
+    fn is_due(&mut self, done: u32, now: Instant) -> bool {
+        let due = done >= self.total
+            || self.last_update.is_none_or(|last| now.duration_since(last) >= UPDATE_INTERVAL);
+        if due {
+            self.last_update = Some(now);
+        }
+        due
+    }
+
+    pub fn update(&mut self, done: u32) {
+        if self.enabled && self.is_due(done, Instant::now()) {
+            let mut err = io::stderr().lock();
+            let _ = write!(err, "\r{}", format_progress(self.title, done, self.total));
+            let _ = err.flush();
+        }
+    }
+
+    pub fn finish(&mut self) {
+        if self.enabled {
+            eprintln!("\r{}, done.", format_progress(self.title, self.total, self.total));
+        }
+    }
+}
+
+fn format_progress(title: &str, done: u32, total: u32) -> String {
+    let percent = match total {
+        0 => 100,
+        total => u64::from(done) * 100 / u64::from(total),
+    };
+    format!("{title}: {percent:>3}% ({done}/{total})")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_progress_shows_percentage_and_counts() {
+        assert_eq!(format_progress("Receiving objects", 0, 250), "Receiving objects:   0% (0/250)");
+        assert_eq!(format_progress("Receiving objects", 83, 250), "Receiving objects:  33% (83/250)");
+        assert_eq!(format_progress("Receiving objects", 250, 250), "Receiving objects: 100% (250/250)");
+        assert_eq!(format_progress("Receiving objects", 0, 0), "Receiving objects: 100% (0/0)");
+    }
+
+    #[test]
+    fn updates_are_rate_limited_except_for_the_last() {
+        let mut progress = Progress::new("Receiving objects", 10, true);
+        let start = Instant::now();
+
+        assert!(progress.is_due(1, start));
+        assert!(!progress.is_due(2, start + Duration::from_millis(50)));
+        assert!(progress.is_due(3, start + Duration::from_millis(100)));
+        assert!(progress.is_due(10, start + Duration::from_millis(110)));
+    }
+}
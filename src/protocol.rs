@@ -0,0 +1,119 @@
+use std::io;
+use std::io::{Read, Write};
+
+pub const MAX_PKT_LINE_LEN: usize = 65520;
+const LENGTH_PREFIX_LEN: usize = 4;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn read_pkt_line<R: Read>(r: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut prefix = [0; LENGTH_PREFIX_LEN];
+    r.read_exact(&mut prefix)?;
+    let length = std::str::from_utf8(&prefix)
+        .ok()
+        .and_then(|prefix| usize::from_str_radix(prefix, 16).ok())
+        .ok_or_else(|| invalid_data("invalid pkt-line length"))?;
+    match length {
+        0 => Ok(None),
+        length if !(LENGTH_PREFIX_LEN..=MAX_PKT_LINE_LEN).contains(&length) => {
+            Err(invalid_data("invalid pkt-line length"))
+        }
+        length => {
+            let mut data = vec![0; length - LENGTH_PREFIX_LEN];
+            r.read_exact(&mut data)?;
+            Ok(Some(data))
+        }
+    }
+}
+
+pub fn write_pkt_line<W: Write>(w: &mut W, data: &[u8]) -> io::Result<()> {
+    let length = data.len() + LENGTH_PREFIX_LEN;
+    if length > MAX_PKT_LINE_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "pkt-line payload too long"));
+    }
+    write!(w, "{length:04x}")?;
+    w.write_all(data)
+}
+
+pub fn write_flush<W: Write>(w: &mut W) -> io::Result<()> {
+    w.write_all(b"0000")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_pkt_line() -> io::Result<()> {
+        let mut out = Vec::new();
+        write_pkt_line(&mut out, b"done\n")?;
+
+        assert_eq!(out, b"0009done\n");
+        Ok(())
+    }
+
+    #[test]
+    fn read_pkt_line_reads_consecutive_lines() -> io::Result<()> {
+        let mut data: &[u8] = b"0009done\n000ahello\n";
+
+        assert_eq!(read_pkt_line(&mut data)?, Some(b"done\n".to_vec()));
+        assert_eq!(read_pkt_line(&mut data)?, Some(b"hello\n".to_vec()));
+        assert!(data.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn read_pkt_line_returns_none_on_flush() -> io::Result<()> {
+        let mut data: &[u8] = b"00000006a\n";
+
+        assert_eq!(read_pkt_line(&mut data)?, None);
+        assert_eq!(read_pkt_line(&mut data)?, Some(b"a\n".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn read_pkt_line_reads_empty_line() -> io::Result<()> {
+        let mut data: &[u8] = b"0004";
+
+        assert_eq!(read_pkt_line(&mut data)?, Some(Vec::new()));
+        Ok(())
+    }
+
+    #[test]
+    fn max_length_payload_round_trips() -> io::Result<()> {
+        let payload = vec![b'x'; MAX_PKT_LINE_LEN - 4];
+        let mut out = Vec::new();
+        write_pkt_line(&mut out, &payload)?;
+
+        assert_eq!(&out[..4], b"fff0");
+        assert_eq!(read_pkt_line(&mut out.as_slice())?, Some(payload));
+        Ok(())
+    }
+
+    #[test]
+    fn write_pkt_line_is_error_with_oversized_payload() {
+        let payload = vec![b'x'; MAX_PKT_LINE_LEN - 3];
+
+        let actual_result = write_pkt_line(&mut Vec::new(), &payload);
+
+        assert_eq!(actual_result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn read_pkt_line_is_error_with_invalid_length() {
+        for data in [&b"00zz"[..], b"0003", b"fff1"] {
+            let actual_result = read_pkt_line(&mut &data[..]);
+
+            assert_eq!(actual_result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn read_pkt_line_is_error_with_truncated_payload() {
+        let actual_result = read_pkt_line(&mut &b"0009do"[..]);
+
+        assert_eq!(actual_result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,853 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use crate::attributes;
+use crate::catfile;
+use crate::catfile::ObjectFormat;
+use crate::commit;
+use crate::commit::{Identity, Timestamp};
+use crate::repo;
+use crate::tag;
+use crate::tree;
+
+const MAX_SYMREF_DEPTH: usize = 5;
+const PACKED_REFS_HEADER: &str = "# pack-refs with: peeled fully-peeled sorted \n";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("ref not found: {0}")]
+    RefNotFound(String),
+    #[error("circular symbolic ref: {0}")]
+    CircularRef(String),
+    #[error("malformed ref: {0}")]
+    MalformedRef(String),
+    #[error("unknown revision: {0}")]
+    UnknownRevision(String),
+    #[error("bad for-each-ref format: {0}")]
+    BadFormat(String),
+    #[error("ref {0} is not a symbolic ref")]
+    NotSymbolic(String),
+    #[error("refusing to point {0} outside of refs/")]
+    OutsideRefs(String),
+    #[error("cannot lock ref '{name}': is at {actual} but expected {expected}")]
+    StaleRef { name: String, actual: String, expected: String },
+}
+
+fn is_pseudo_ref(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+}
+
+fn ref_candidates(name: &str) -> Vec<String> {
+    if is_pseudo_ref(name) || name.starts_with("refs/") {
+        return vec![name.to_string()];
+    }
+    ["refs", "refs/tags", "refs/heads", "refs/remotes"]
+        .iter()
+        .map(|prefix| format!("{prefix}/{name}"))
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct PackedRef {
+    pub name: String,
+    pub hash: String,
+    pub peeled: Option<String>,
+}
+
+fn parse_packed_refs(content: &str) -> Vec<PackedRef> {
+    let mut refs: Vec<PackedRef> = Vec::new();
+    for line in content.lines().filter(|line| !line.starts_with('#')) {
+        if let Some(peeled) = line.strip_prefix('^') {
+            if let Some(last) = refs.last_mut() {
+                last.peeled = Some(peeled.trim().to_ascii_lowercase());
+            }
+        } else if let Some((hash, name)) = line.split_once(' ') {
+            refs.push(PackedRef { name: name.trim().to_string(), hash: hash.to_ascii_lowercase(), peeled: None });
+        }
+    }
+    refs
+}
+
+pub(crate) fn read_packed_refs(git_dir: &Path) -> Vec<PackedRef> {
+    fs::read_to_string(git_dir.join("packed-refs"))
+        .map(|content| parse_packed_refs(&content))
+        .unwrap_or_default()
+}
+
+fn read_ref(git_dir: &Path, name: &str, packed: &[PackedRef]) -> Option<String> {
+    ref_candidates(name).into_iter().find_map(|candidate| {
+        let path = git_dir.join(&candidate);
+        if path.is_file() {
+            return fs::read_to_string(path).ok();
+        }
+        packed.iter().find(|packed| packed.name == candidate).map(|packed| packed.hash.clone())
+    })
+}
+
+pub(crate) fn resolve_ref_in(git_dir: &Path, name: &str) -> Result<String, Error> {
+    let hex_len = ObjectFormat::of(git_dir).hex_len();
+    let mut seen: Vec<String> = Vec::new();
+    let packed = read_packed_refs(git_dir);
+    let mut current = name.to_string();
+    loop {
+        if seen.contains(&current) || seen.len() > MAX_SYMREF_DEPTH {
+            return Err(Error::CircularRef(name.to_string()));
+        }
+        let content = read_ref(git_dir, &current, &packed).ok_or(Error::RefNotFound(current.clone()))?;
+        let content = content.trim();
+        seen.push(current);
+        match content.strip_prefix("ref: ") {
+            Some(target) => current = target.to_string(),
+            None if content.len() == hex_len && content.chars().all(|c| c.is_ascii_hexdigit()) => {
+                return Ok(content.to_ascii_lowercase());
+            }
+            None => return Err(Error::MalformedRef(seen.pop().unwrap_or_default())),
+        }
+    }
+}
+
+pub fn resolve_ref(name: &str) -> anyhow::Result<String> {
+    Ok(resolve_ref_in(&repo::git_dir()?, name)?)
+}
+
+pub(crate) fn rev_parse_in(git_dir: &Path, name: &str) -> Result<String, Error> {
+    match resolve_ref_in(git_dir, name) {
+        Err(Error::RefNotFound(_)) => {
+            catfile::resolve_hash_in(git_dir, name).map_err(|_|Error::UnknownRevision(name.to_string()))
+        }
+        result => result,
+    }
+}
+
+pub fn rev_parse(name: &str) -> anyhow::Result<String> {
+    if let Some((rev, path)) = name.split_once(':') {
+        let hash = tag::peel_tag(&rev_parse(rev)?)?;
+        let tree = match catfile::object_type(&hash)? {
+            catfile::ObjectType::Commit => commit::parse_commit(&hash)?.tree,
+            _ => hash,
+        };
+        return Ok(tree::lookup_path(&tree, path)?.hash);
+    }
+    if let Some(base) = name.strip_suffix("^{tree}") {
+        let hash = tag::peel_tag(&rev_parse(base)?)?;
+        return match catfile::object_type(&hash)? {
+            catfile::ObjectType::Commit => Ok(commit::parse_commit(&hash)?.tree),
+            _ => Ok(hash),
+        };
+    }
+    if let Some(base) = name.strip_suffix("^{}") {
+        return tag::peel_tag(&rev_parse(base)?);
+    }
+    Ok(rev_parse_in(&repo::git_dir()?, name)?)
+}
+
+fn full_ref_name(name: &str) -> String {
+    if is_pseudo_ref(name) || name.starts_with("refs/") {
+        name.to_string()
+    } else {
+        format!("refs/heads/{name}")
+    }
+}
+
+fn ref_path(git_dir: &Path, name: &str) -> PathBuf {
+    git_dir.join(full_ref_name(name))
+}
+
+fn reflog_path(git_dir: &Path, name: &str) -> PathBuf {
+    git_dir.join("logs").join(full_ref_name(name))
+}
+
+fn should_log(git_dir: &Path, name: &str) -> bool {
+    let name = full_ref_name(name);
+    name == "HEAD"
+        || ["refs/heads/", "refs/remotes/", "refs/notes/"].iter().any(|prefix| name.starts_with(prefix))
+        || reflog_path(git_dir, &name).is_file()
+}
+
+pub(crate) fn append_reflog_in(git_dir: &Path, name: &str, old: Option<&str>, new: &str, message: &str) -> anyhow::Result<()> {
+    if !should_log(git_dir, name) {
+        return Ok(());
+    }
+    let zero = "0".repeat(ObjectFormat::of(git_dir).hex_len());
+    let identity = Identity::committer().signature(Timestamp::committer()?);
+    let path = reflog_path(git_dir, name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut log = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(log, "{} {new} {identity}\t{message}", old.unwrap_or(&zero))?;
+    Ok(())
+}
+
+fn format_reflog(name: &str, content: &str) -> Vec<String> {
+    content
+        .lines()
+        .rev()
+        .filter_map(|line| {
+            let (info, message) = line.split_once('\t').unwrap_or((line, ""));
+            Some((info.split(' ').nth(1)?, message))
+        })
+        .enumerate()
+        .map(|(n, (hash, message))| format!("{} {name}@{{{n}}}: {message}", catfile::abbreviate(hash, 7)))
+        .collect()
+}
+
+pub fn reflog(name: &str) -> anyhow::Result<()> {
+    let content = match fs::read_to_string(reflog_path(&repo::git_dir()?, name)) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    for line in format_reflog(name, &content) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+pub(crate) fn symbolic_target_in(git_dir: &Path, name: &str) -> io::Result<Option<String>> {
+    let content = fs::read_to_string(ref_path(git_dir, name))?;
+    Ok(content.trim().strip_prefix("ref: ").map(str::to_string))
+}
+
+pub(crate) fn update_ref_in(git_dir: &Path, name: &str, hash: &str, message: &str) -> anyhow::Result<()> {
+    let old = resolve_ref_in(git_dir, &full_ref_name(name)).ok();
+    let path = ref_path(git_dir, name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, format!("{hash}\n"))?;
+    append_reflog_in(git_dir, name, old.as_deref(), hash, message)
+}
+
+pub(crate) fn write_symbolic_ref_in(git_dir: &Path, name: &str, target: &str) -> io::Result<()> {
+    fs::write(ref_path(git_dir, name), format!("ref: {target}\n"))
+}
+
+fn remove_packed_ref(content: &str, name: &str) -> Option<String> {
+    let mut found = false;
+    let mut removing = false;
+    let mut kept = String::new();
+    for line in content.lines() {
+        if line.starts_with('^') && removing {
+            continue;
+        }
+        removing = !line.starts_with('#') && line.split_once(' ').is_some_and(|(_, packed)| packed.trim() == name);
+        found |= removing;
+        if !removing {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    found.then_some(kept)
+}
+
+pub(crate) fn delete_ref_in(git_dir: &Path, name: &str) -> anyhow::Result<String> {
+    let name = full_ref_name(name);
+    let hash = resolve_ref_in(git_dir, &name)?;
+    let path = ref_path(git_dir, &name);
+    if path.is_file() {
+        fs::remove_file(path)?;
+    }
+    let packed_path = git_dir.join("packed-refs");
+    if let Some(content) = fs::read_to_string(&packed_path).ok().and_then(|content| remove_packed_ref(&content, &name)) {
+        fs::write(packed_path, content)?;
+    }
+    let log = reflog_path(git_dir, &name);
+    if log.is_file() {
+        fs::remove_file(log)?;
+    }
+    Ok(hash)
+}
+
+fn format_packed_refs(refs: &[PackedRef]) -> String {
+    let mut content = PACKED_REFS_HEADER.to_string();
+    for packed in refs {
+        content.push_str(&format!("{} {}\n", packed.hash, packed.name));
+        if let Some(peeled) = &packed.peeled {
+            content.push_str(&format!("^{peeled}\n"));
+        }
+    }
+    content
+}
+
+fn remove_empty_ref_dirs(git_dir: &Path, path: &Path) {
+    let top_level = git_dir.join("refs");
+    let mut dir = path.parent();
+    while let Some(current) = dir.filter(|dir| dir.parent().is_some_and(|parent| parent != top_level && parent.starts_with(&top_level))) {
+        if fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+pub(crate) fn pack_refs_in(git_dir: &Path, all: bool) -> anyhow::Result<usize> {
+    let hex_len = ObjectFormat::of(git_dir).hex_len();
+    let mut loose = Vec::new();
+    if git_dir.join("refs").is_dir() {
+        collect_refs(git_dir, &git_dir.join("refs"), &mut loose)?;
+    }
+    let mut packed = read_packed_refs(git_dir);
+    let mut packed_now = Vec::new();
+    for name in loose.into_iter().filter(|name| all || name.starts_with("refs/tags/")) {
+        let content = fs::read_to_string(git_dir.join(&name))?;
+        let hash = content.trim().to_ascii_lowercase();
+        if hash.len() != hex_len || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            continue;
+        }
+        let peeled = match catfile::object_type_in(git_dir, &hash)? {
+            catfile::ObjectType::Tag => Some(tag::peel_tag_in(git_dir, &hash)?),
+            _ => None,
+        };
+        packed.retain(|existing| existing.name != name);
+        packed.push(PackedRef { name: name.clone(), hash, peeled });
+        packed_now.push(name);
+    }
+    packed.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let lock = git_dir.join("packed-refs.lock");
+    fs::write(&lock, format_packed_refs(&packed))?;
+    fs::rename(&lock, git_dir.join("packed-refs"))?;
+    for name in &packed_now {
+        let path = git_dir.join(name);
+        fs::remove_file(&path)?;
+        remove_empty_ref_dirs(git_dir, &path);
+    }
+    Ok(packed_now.len())
+}
+
+pub fn pack_refs(all: bool) -> anyhow::Result<()> {
+    pack_refs_in(&repo::git_dir()?, all).map(|_| ())
+}
+
+fn check_old_value_in(git_dir: &Path, name: &str, expected: Option<&str>) -> Result<(), Error> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let name = full_ref_name(name);
+    let actual = resolve_ref_in(git_dir, &name).ok();
+    let expected_hash = Some(expected).filter(|hash| !hash.is_empty() && !hash.chars().all(|c| c == '0'));
+    if actual.as_deref() == expected_hash {
+        return Ok(());
+    }
+    let zero = "0".repeat(ObjectFormat::of(git_dir).hex_len());
+    Err(Error::StaleRef { name, actual: actual.unwrap_or_else(|| zero.clone()), expected: expected_hash.map_or(zero, str::to_string) })
+}
+
+pub(crate) fn update_head_in(git_dir: &Path, hash: &str, message: &str) -> anyhow::Result<()> {
+    match symbolic_target_in(git_dir, "HEAD")? {
+        Some(target) => {
+            let old = resolve_ref_in(git_dir, "HEAD").ok();
+            update_ref_in(git_dir, &target, hash, message)?;
+            append_reflog_in(git_dir, "HEAD", old.as_deref(), hash, message)
+        }
+        None => update_ref_in(git_dir, "HEAD", hash, message),
+    }
+}
+
+fn collect_refs(git_dir: &Path, dir: &Path, refs: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_refs(git_dir, &path, refs)?;
+        } else if let Ok(name) = path.strip_prefix(git_dir) {
+            refs.push(name.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn list_refs_in(git_dir: &Path, prefix: &str) -> io::Result<Vec<String>> {
+    let mut refs = Vec::new();
+    let dir = git_dir.join(prefix);
+    if dir.is_dir() {
+        collect_refs(git_dir, &dir, &mut refs)?;
+    }
+    let prefix = format!("{}/", prefix.trim_end_matches('/'));
+    refs.extend(read_packed_refs(git_dir).into_iter().map(|packed| packed.name).filter(|name| name.starts_with(&prefix)));
+    refs.sort();
+    refs.dedup();
+    Ok(refs)
+}
+
+pub(crate) fn all_refs_in(git_dir: &Path) -> Result<Vec<(String, String)>, Error> {
+    let names = list_refs_in(git_dir, "refs").map_err(|_|Error::RefNotFound("refs".to_string()))?;
+    names.into_iter().map(|name| Ok((resolve_ref_in(git_dir, &name)?, name))).collect()
+}
+
+fn show_ref_lines_in(git_dir: &Path, heads: bool, tags: bool, head: bool) -> Result<Vec<String>, Error> {
+    let mut lines = Vec::new();
+    if head {
+        if let Ok(hash) = resolve_ref_in(git_dir, "HEAD") {
+            lines.push(format!("{hash} HEAD"));
+        }
+    }
+    for (hash, name) in all_refs_in(git_dir)? {
+        let selected = (!heads && !tags)
+            || (heads && name.starts_with("refs/heads/"))
+            || (tags && name.starts_with("refs/tags/"));
+        if selected {
+            lines.push(format!("{hash} {name}"));
+        }
+    }
+    Ok(lines)
+}
+
+const DEFAULT_REF_FORMAT: &str = "%(objectname) %(objecttype)\t%(refname)";
+
+fn ref_matches(pattern: &str, name: &str) -> bool {
+    let prefix = pattern.trim_end_matches('/');
+    name == prefix
+        || name.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+        || attributes::glob_match(pattern.as_bytes(), name.as_bytes())
+}
+
+fn short_ref_name(name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
+fn format_ref(format: &str, name: &str, hash: &str, object_type: &str) -> Result<String, Error> {
+    let mut line = String::new();
+    let mut rest = format;
+    while let Some(position) = rest.find('%') {
+        line.push_str(&rest[..position]);
+        rest = &rest[position..];
+        if let Some(after) = rest.strip_prefix("%%") {
+            line.push('%');
+            rest = after;
+            continue;
+        }
+        let end = rest.find(')').filter(|_| rest.starts_with("%(")).ok_or_else(|| Error::BadFormat(rest.to_string()))?;
+        match &rest[2..end] {
+            "refname" => line.push_str(name),
+            "refname:short" => line.push_str(short_ref_name(name)),
+            "objectname" => line.push_str(hash),
+            "objecttype" => line.push_str(object_type),
+            _ => return Err(Error::BadFormat(rest[..=end].to_string())),
+        }
+        rest = &rest[end + 1..];
+    }
+    line.push_str(rest);
+    Ok(line)
+}
+
+pub fn for_each_ref(pattern: Option<&str>, format: Option<&str>) -> anyhow::Result<()> {
+    let format = format.unwrap_or(DEFAULT_REF_FORMAT);
+    for (hash, name) in all_refs_in(&repo::git_dir()?)? {
+        if pattern.is_some_and(|pattern| !ref_matches(pattern, &name)) {
+            continue;
+        }
+        let object_type = catfile::object_type(&hash)?;
+        println!("{}", format_ref(format, &name, &hash, object_type.as_str())?);
+    }
+    Ok(())
+}
+
+pub fn show_ref(heads: bool, tags: bool, head: bool) -> anyhow::Result<bool> {
+    let lines = show_ref_lines_in(&repo::git_dir()?, heads, tags, head)?;
+    for line in &lines {
+        println!("{line}");
+    }
+    Ok(!lines.is_empty())
+}
+
+pub fn list_refs(prefix: &str) -> anyhow::Result<Vec<String>> {
+    Ok(list_refs_in(&repo::git_dir()?, prefix)?)
+}
+
+pub fn symbolic_target(name: &str) -> anyhow::Result<Option<String>> {
+    Ok(symbolic_target_in(&repo::git_dir()?, name)?)
+}
+
+pub fn update_ref(name: &str, hash: &str, message: &str) -> anyhow::Result<()> {
+    update_ref_in(&repo::git_dir()?, name, hash, message)
+}
+
+pub fn write_symbolic_ref(name: &str, target: &str) -> anyhow::Result<()> {
+    Ok(write_symbolic_ref_in(&repo::git_dir()?, name, target)?)
+}
+
+pub fn update_head(hash: &str, message: &str) -> anyhow::Result<()> {
+    update_head_in(&repo::git_dir()?, hash, message)
+}
+
+fn resolve_value(git_dir: &Path, value: &str) -> Result<String, Error> {
+    if !value.is_empty() && value.chars().all(|c| c == '0') {
+        return Ok(value.to_string());
+    }
+    rev_parse_in(git_dir, value)
+}
+
+pub fn update_ref_checked(name: &str, new: &str, old: Option<&str>, message: &str) -> anyhow::Result<()> {
+    let git_dir = repo::git_dir()?;
+    let new = rev_parse_in(&git_dir, new)?;
+    let old = old.map(|old| resolve_value(&git_dir, old)).transpose()?;
+    check_old_value_in(&git_dir, name, old.as_deref())?;
+    if name == "HEAD" {
+        return update_head_in(&git_dir, &new, message);
+    }
+    update_ref_in(&git_dir, name, &new, message)
+}
+
+pub fn delete_ref(name: &str, old: Option<&str>) -> anyhow::Result<()> {
+    let git_dir = repo::git_dir()?;
+    let old = old.map(|old| resolve_value(&git_dir, old)).transpose()?;
+    check_old_value_in(&git_dir, name, old.as_deref())?;
+    delete_ref_in(&git_dir, name).map(|_| ())
+}
+
+fn read_symbolic_ref_in(git_dir: &Path, name: &str, short: bool) -> Result<String, Error> {
+    let target = symbolic_target_in(git_dir, name)
+        .ok()
+        .flatten()
+        .ok_or_else(|| Error::NotSymbolic(name.to_string()))?;
+    if short {
+        return Ok(target.strip_prefix("refs/heads/").unwrap_or(&target).to_string());
+    }
+    Ok(target)
+}
+
+pub fn symbolic_ref(name: &str, value: Option<&str>, short: bool) -> anyhow::Result<()> {
+    let git_dir = repo::git_dir()?;
+    match value {
+        Some(target) if !target.starts_with("refs/") => Err(Error::OutsideRefs(name.to_string()).into()),
+        Some(target) => Ok(write_symbolic_ref_in(&git_dir, name, target)?),
+        None => {
+            println!("{}", read_symbolic_ref_in(&git_dir, name, short)?);
+            Ok(())
+        }
+    }
+}
+
+pub fn append_reflog(name: &str, old: Option<&str>, new: &str, message: &str) -> anyhow::Result<()> {
+    append_reflog_in(&repo::git_dir()?, name, old, new, message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HASH: &str = "ab3e94d6b7ec38af9d3dee5b424a13a292742c4c";
+
+    fn git_dir_with(name: &str, refs: &[(&str, &str)]) -> PathBuf {
+        let git_dir = std::env::temp_dir().join(name).join(".git");
+        let _ = fs::remove_dir_all(&git_dir);
+        for (ref_name, content) in refs {
+            let path = git_dir.join(ref_name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, format!("{content}\n")).unwrap();
+        }
+        git_dir
+    }
+
+    #[test]
+    fn resolve_ref_follows_head_to_branch() -> Result<(), Error> {
+        let git_dir = git_dir_with("resolve_ref_head", &[
+            ("HEAD", "ref: refs/heads/main"),
+            ("refs/heads/main", HASH),
+        ]);
+
+        let hash = resolve_ref_in(&git_dir, "HEAD")?;
+        assert_eq!(hash, HASH);
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_ref_finds_short_branch_name() -> Result<(), Error> {
+        let git_dir = git_dir_with("resolve_ref_short_name", &[("refs/heads/feature/x", HASH)]);
+
+        let hash = resolve_ref_in(&git_dir, "feature/x")?;
+        assert_eq!(hash, HASH);
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_ref_is_error_with_unborn_branch() {
+        let git_dir = git_dir_with("resolve_ref_unborn", &[("HEAD", "ref: refs/heads/main")]);
+        let expected_error = Err(Error::RefNotFound("refs/heads/main".to_string()));
+
+        let actual_result = resolve_ref_in(&git_dir, "HEAD");
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn resolve_ref_is_error_with_circular_refs() {
+        let git_dir = git_dir_with("resolve_ref_circular", &[
+            ("refs/heads/a", "ref: refs/heads/b"),
+            ("refs/heads/b", "ref: refs/heads/a"),
+        ]);
+        let expected_error = Err(Error::CircularRef("a".to_string()));
+
+        let actual_result = resolve_ref_in(&git_dir, "a");
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn resolve_ref_is_error_with_malformed_ref() {
+        let git_dir = git_dir_with("resolve_ref_malformed", &[("refs/heads/main", "not-a-hash")]);
+        let expected_error = Err(Error::MalformedRef("main".to_string()));
+
+        let actual_result = resolve_ref_in(&git_dir, "main");
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn update_ref_creates_nested_directories() -> Result<(), Error> {
+        let git_dir = git_dir_with("update_ref_nested", &[]);
+
+        update_ref_in(&git_dir, "refs/heads/feature/deep/x", HASH, "branch: Created from HEAD").unwrap();
+        assert_eq!(fs::read_to_string(git_dir.join("refs/heads/feature/deep/x")).unwrap(), format!("{HASH}\n"));
+        assert_eq!(resolve_ref_in(&git_dir, "feature/deep/x")?, HASH);
+        Ok(())
+    }
+
+    #[test]
+    fn update_head_moves_current_branch() -> Result<(), Error> {
+        let git_dir = git_dir_with("update_head_branch", &[("HEAD", "ref: refs/heads/main")]);
+
+        update_head_in(&git_dir, HASH, "commit: test").unwrap();
+        assert_eq!(resolve_ref_in(&git_dir, "main")?, HASH);
+        assert_eq!(fs::read_to_string(git_dir.join("HEAD")).unwrap(), "ref: refs/heads/main\n");
+        Ok(())
+    }
+
+    #[test]
+    fn update_head_with_detached_head_rewrites_head() {
+        let git_dir = git_dir_with("update_head_detached", &[("HEAD", "6255d532c5299a066ba45a9aa394aebb96049e06")]);
+
+        update_head_in(&git_dir, HASH, "commit: test").unwrap();
+        assert_eq!(fs::read_to_string(git_dir.join("HEAD")).unwrap(), format!("{HASH}\n"));
+    }
+
+    #[test]
+    fn list_refs_walks_nested_directories_in_order() {
+        let git_dir = git_dir_with("list_refs_nested", &[
+            ("refs/heads/main", HASH),
+            ("refs/heads/feature/x", HASH),
+            ("refs/tags/v1", HASH),
+        ]);
+        let expected_refs = vec!["refs/heads/feature/x".to_string(), "refs/heads/main".to_string()];
+
+        let refs = list_refs_in(&git_dir, "refs/heads").unwrap();
+        assert_eq!(refs, expected_refs);
+    }
+
+    #[test]
+    fn rev_parse_resolves_refs_and_tags() -> Result<(), Error> {
+        let git_dir = git_dir_with("rev_parse_refs", &[
+            ("HEAD", "ref: refs/heads/main"),
+            ("refs/heads/main", HASH),
+            ("refs/tags/v1", "6255d532c5299a066ba45a9aa394aebb96049e06"),
+        ]);
+
+        assert_eq!(rev_parse_in(&git_dir, "HEAD")?, HASH);
+        assert_eq!(rev_parse_in(&git_dir, "refs/heads/main")?, HASH);
+        assert_eq!(rev_parse_in(&git_dir, "v1")?, "6255d532c5299a066ba45a9aa394aebb96049e06");
+        Ok(())
+    }
+
+    #[test]
+    fn rev_parse_falls_back_to_short_hash() -> Result<(), Error> {
+        let git_dir = git_dir_with("rev_parse_short_hash", &[("objects/ab/3e94d6b7ec38af9d3dee5b424a13a292742c4c", "")]);
+
+        assert_eq!(rev_parse_in(&git_dir, "ab3e94")?, HASH);
+        Ok(())
+    }
+
+    #[test]
+    fn rev_parse_is_error_with_unknown_name() {
+        let git_dir = git_dir_with("rev_parse_unknown", &[("HEAD", "ref: refs/heads/main")]);
+        let expected_error = Err(Error::UnknownRevision("nope".to_string()));
+
+        let actual_result = rev_parse_in(&git_dir, "nope");
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn ref_candidates_do_not_search_git_dir_for_branch_names() {
+        let candidates = ref_candidates("config");
+
+        assert!(!candidates.contains(&"config".to_string()));
+    }
+
+    #[test]
+    fn test_parse_packed_refs() {
+        let content = format!("# pack-refs with: peeled fully-peeled sorted \n{HASH} refs/heads/main\n{HASH} refs/tags/v1\n^{}\n", "cd".repeat(20));
+        let expected_refs = vec![
+            PackedRef { name: "refs/heads/main".to_string(), hash: HASH.to_string(), peeled: None },
+            PackedRef { name: "refs/tags/v1".to_string(), hash: HASH.to_string(), peeled: Some("cd".repeat(20)) },
+        ];
+
+        assert_eq!(parse_packed_refs(&content), expected_refs);
+    }
+
+    #[test]
+    fn resolve_ref_reads_packed_branch_and_annotated_tag() -> Result<(), Error> {
+        let tag = "cd".repeat(20);
+        let git_dir = git_dir_with("resolve_ref_packed", &[
+            ("HEAD", "ref: refs/heads/main"),
+            ("packed-refs", &format!("# pack-refs with: peeled\n{HASH} refs/heads/main\n{tag} refs/tags/v1\n^{HASH}")),
+        ]);
+
+        assert_eq!(resolve_ref_in(&git_dir, "HEAD")?, HASH);
+        assert_eq!(resolve_ref_in(&git_dir, "v1")?, tag);
+        assert_eq!(list_refs_in(&git_dir, "refs/heads").unwrap(), ["refs/heads/main"]);
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_ref_prefers_loose_ref_over_packed() -> Result<(), Error> {
+        let git_dir = git_dir_with("resolve_ref_loose_over_packed", &[
+            ("refs/heads/main", HASH),
+            ("packed-refs", &format!("{} refs/heads/main", "cd".repeat(20))),
+        ]);
+
+        assert_eq!(resolve_ref_in(&git_dir, "main")?, HASH);
+        Ok(())
+    }
+
+    #[test]
+    fn show_ref_lists_loose_and_packed_refs() -> Result<(), Error> {
+        let other = "cd".repeat(20);
+        let git_dir = git_dir_with("show_ref_loose_and_packed", &[
+            ("HEAD", "ref: refs/heads/main"),
+            ("refs/heads/main", HASH),
+            ("packed-refs", &format!("{other} refs/heads/main\n{other} refs/tags/v1\n")),
+        ]);
+
+        assert_eq!(show_ref_lines_in(&git_dir, false, false, false)?, [
+            format!("{HASH} refs/heads/main"),
+            format!("{other} refs/tags/v1"),
+        ]);
+        assert_eq!(show_ref_lines_in(&git_dir, false, true, true)?, [
+            format!("{HASH} HEAD"),
+            format!("{other} refs/tags/v1"),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn update_head_appends_reflog_for_branch_and_head() {
+        let git_dir = git_dir_with("update_head_reflog", &[("HEAD", "ref: refs/heads/main")]);
+        let second = "6255d532c5299a066ba45a9aa394aebb96049e06";
+
+        update_head_in(&git_dir, HASH, "commit (initial): first").unwrap();
+        update_head_in(&git_dir, second, "commit: second").unwrap();
+        for log in ["logs/HEAD", "logs/refs/heads/main"] {
+            let content = fs::read_to_string(git_dir.join(log)).unwrap();
+            let lines: Vec<&str> = content.lines().collect();
+            assert_eq!(lines.len(), 2);
+            assert!(lines[0].starts_with(&format!("{} {HASH} ", "0".repeat(40))));
+            assert!(lines[0].ends_with("\tcommit (initial): first"));
+            assert!(lines[1].starts_with(&format!("{HASH} {second} ")));
+        }
+    }
+
+    #[test]
+    fn format_reflog_lists_newest_first() {
+        let content = format!(
+            "{zero} {HASH} A U Thor <author@example.com> 1700000000 +0000\tcommit (initial): first\n\
+             {HASH} 6255d532c5299a066ba45a9aa394aebb96049e06 A U Thor <author@example.com> 1700000100 +0000\tcommit: second\n",
+            zero = "0".repeat(40),
+        );
+        let expected_lines = ["6255d53 HEAD@{0}: commit: second", "ab3e94d HEAD@{1}: commit (initial): first"];
+
+        assert_eq!(format_reflog("HEAD", &content), expected_lines);
+    }
+
+    #[test]
+    fn delete_ref_removes_loose_and_packed_entries() -> Result<(), Error> {
+        let other = "6255d532c5299a066ba45a9aa394aebb96049e06";
+        let git_dir = git_dir_with("delete_ref_packed", &[
+            ("refs/tags/v1", HASH),
+            ("packed-refs", &format!("# pack-refs with: peeled\n{HASH} refs/tags/v1\n^{other}\n{other} refs/tags/v2")),
+        ]);
+
+        assert_eq!(delete_ref_in(&git_dir, "refs/tags/v1").unwrap(), HASH);
+        assert!(!git_dir.join("refs/tags/v1").exists());
+        assert_eq!(fs::read_to_string(git_dir.join("packed-refs")).unwrap(), format!("# pack-refs with: peeled\n{other} refs/tags/v2\n"));
+        assert_eq!(resolve_ref_in(&git_dir, "refs/tags/v1"), Err(Error::RefNotFound("refs/tags/v1".to_string())));
+        Ok(())
+    }
+
+    #[test]
+    fn check_old_value_compares_current_hash() {
+        let git_dir = git_dir_with("check_old_value", &[("refs/heads/main", HASH)]);
+        let other = "6255d532c5299a066ba45a9aa394aebb96049e06";
+        let zero = "0".repeat(40);
+
+        assert_eq!(check_old_value_in(&git_dir, "refs/heads/main", Some(HASH)), Ok(()));
+        assert_eq!(check_old_value_in(&git_dir, "refs/heads/main", None), Ok(()));
+        assert_eq!(check_old_value_in(&git_dir, "refs/heads/dev", Some(&zero)), Ok(()));
+        assert_eq!(
+            check_old_value_in(&git_dir, "main", Some(other)),
+            Err(Error::StaleRef { name: "refs/heads/main".to_string(), actual: HASH.to_string(), expected: other.to_string() }),
+        );
+        assert_eq!(
+            check_old_value_in(&git_dir, "refs/heads/main", Some(&zero)),
+            Err(Error::StaleRef { name: "refs/heads/main".to_string(), actual: HASH.to_string(), expected: zero.clone() }),
+        );
+    }
+
+    #[test]
+    fn read_symbolic_ref_returns_target() {
+        let git_dir = git_dir_with("read_symbolic_ref", &[("HEAD", "ref: refs/heads/main"), ("ORIG_HEAD", HASH)]);
+
+        assert_eq!(read_symbolic_ref_in(&git_dir, "HEAD", false), Ok("refs/heads/main".to_string()));
+        assert_eq!(read_symbolic_ref_in(&git_dir, "HEAD", true), Ok("main".to_string()));
+        assert_eq!(read_symbolic_ref_in(&git_dir, "ORIG_HEAD", false), Err(Error::NotSymbolic("ORIG_HEAD".to_string())));
+    }
+
+    #[test]
+    fn ref_matches_prefixes_and_globs() {
+        assert!(ref_matches("refs/heads", "refs/heads/main"));
+        assert!(ref_matches("refs/heads/", "refs/heads/feature/x"));
+        assert!(ref_matches("refs/tags/v1.*", "refs/tags/v1.2"));
+        assert!(!ref_matches("refs/head", "refs/heads/main"));
+        assert!(!ref_matches("refs/tags", "refs/heads/main"));
+    }
+
+    #[test]
+    fn format_ref_substitutes_placeholders() {
+        let expected_line = format!("main refs/heads/main commit {HASH} 100%");
+
+        let line = format_ref("%(refname:short) %(refname) %(objecttype) %(objectname) 100%%", "refs/heads/main", HASH, "commit");
+        assert_eq!(line, Ok(expected_line));
+        assert_eq!(format_ref("%(upstream)", "refs/heads/main", HASH, "commit"), Err(Error::BadFormat("%(upstream)".to_string())));
+    }
+
+    #[test]
+    fn pack_refs_packs_fifty_tags_and_peels_annotated_ones() -> anyhow::Result<()> {
+        let git_dir = git_dir_with("pack_refs_tags", &[("HEAD", "ref: refs/heads/main")]);
+        fs::create_dir_all(git_dir.join("objects"))?;
+        fs::create_dir_all(git_dir.join("refs/remotes/origin"))?;
+
+        let blob = catfile::write_object_content_in(&git_dir, catfile::ObjectType::Blob, b"payload")?;
+        let tag_object = format!("object {blob}\ntype blob\ntag annotated\n\nnote\n");
+        let annotated = catfile::write_object_content_in(&git_dir, catfile::ObjectType::Tag, tag_object.as_bytes())?;
+        for n in 0..50 {
+            update_ref_in(&git_dir, &format!("refs/tags/v{n:02}"), &blob, "")?;
+        }
+        update_ref_in(&git_dir, "refs/tags/annotated", &annotated, "")?;
+        update_ref_in(&git_dir, "refs/heads/main", &blob, "")?;
+        write_symbolic_ref_in(&git_dir, "refs/remotes/origin/HEAD", "refs/remotes/origin/main")?;
+        let packed = pack_refs_in(&git_dir, true)?;
+
+        assert_eq!(packed, 52);
+        assert!(!git_dir.join("refs/tags/v07").exists());
+        assert!(!git_dir.join("refs/heads/main").exists());
+        assert!(git_dir.join("refs/remotes/origin/HEAD").is_file());
+        assert_eq!(resolve_ref_in(&git_dir, "v49")?, blob);
+        assert_eq!(resolve_ref_in(&git_dir, "HEAD")?, blob);
+        let content = fs::read_to_string(git_dir.join("packed-refs"))?;
+        assert!(content.starts_with(PACKED_REFS_HEADER));
+        assert!(content.contains(&format!("{annotated} refs/tags/annotated\n^{blob}\n")));
+        assert_eq!(read_packed_refs(&git_dir).len(), 52);
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use crate::config;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("not a git repository (or any of the parent directories): .git")]
+    NotAGitRepository,
+    #[error("this operation must be run in a work tree")]
+    BareRepository,
+    #[error("invalid gitfile format: {0}")]
+    InvalidGitFile(String),
+}
+
+fn read_git_file(path: &Path) -> Result<PathBuf, Error> {
+    let invalid = || Error::InvalidGitFile(path.display().to_string());
+    let content = fs::read_to_string(path).map_err(|_|invalid())?;
+    let target = content.lines().next().and_then(|line| line.strip_prefix("gitdir: ")).ok_or_else(invalid)?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    Ok(base.join(target.trim()))
+}
+
+/// Returns the git directory and the work tree root it was found from.
+fn find_repository_from(start: &Path) -> Result<(PathBuf, PathBuf), Error> {
+    for dir in start.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Ok((dot_git, dir.to_path_buf()));
+        }
+        if dot_git.is_file() {
+            return Ok((read_git_file(&dot_git)?, dir.to_path_buf()));
+        }
+    }
+    Err(Error::NotAGitRepository)
+}
+
+fn find_git_dir_from(start: &Path) -> Result<PathBuf, Error> {
+    find_repository_from(start).map(|(git_dir, _)| git_dir)
+}
+
+pub fn git_dir() -> anyhow::Result<PathBuf> {
+    if let Some(git_dir) = env::var_os("GIT_DIR") {
+        return Ok(PathBuf::from(git_dir));
+    }
+    Ok(find_git_dir_from(&env::current_dir()?)?)
+}
+
+fn work_tree_of(git_dir: &Path, root: &Path) -> Result<PathBuf, Error> {
+    let bare = config::get_in(git_dir, "core.bare").and_then(|value| config::parse_bool(&value));
+    if bare == Some(true) {
+        return Err(Error::BareRepository);
+    }
+    Ok(root.to_path_buf())
+}
+
+pub fn work_tree() -> anyhow::Result<PathBuf> {
+    if env::var_os("GIT_DIR").is_some() {
+        return match env::var_os("GIT_WORK_TREE") {
+            Some(work_tree) => Ok(PathBuf::from(work_tree)),
+            None => Ok(env::current_dir()?),
+        };
+    }
+    let (git_dir, root) = find_repository_from(&env::current_dir()?)?;
+    Ok(work_tree_of(&git_dir, &root)?)
+}
+
+pub(crate) fn shallow_commits_in(git_dir: &Path) -> HashSet<String> {
+    fs::read_to_string(git_dir.join("shallow"))
+        .map(|content| content.lines().map(|line| line.trim().to_ascii_lowercase()).filter(|line| !line.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Commits listed in `.git/shallow`, whose parents were cut off by `clone --depth`.
+pub fn shallow_commits() -> anyhow::Result<HashSet<String>> {
+    Ok(shallow_commits_in(&git_dir()?))
+}
+
+#[cfg(test)]
+pub(crate) static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn find_git_dir_from_walks_up_from_subdirectory() -> Result<(), Error> {
+        let root = env::temp_dir().join("find_git_dir_from_subdirectory");
+        let subdirectory = root.join("src/nested");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(&subdirectory).unwrap();
+
+        let git_dir = find_git_dir_from(&subdirectory)?;
+        assert_eq!(git_dir, root.join(".git"));
+        Ok(())
+    }
+
+    #[test]
+    fn find_git_dir_from_is_error_outside_repository() {
+        let directory = env::temp_dir().join("find_git_dir_from_outside_repository");
+        fs::create_dir_all(&directory).unwrap();
+        let expected_error = Err(Error::NotAGitRepository);
+
+        let actual_result = find_git_dir_from(&directory);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn work_tree_of_is_error_in_bare_repository() {
+        let git_dir = env::temp_dir().join("work_tree_of_bare_repository");
+        fs::create_dir_all(&git_dir).unwrap();
+        fs::write(git_dir.join("config"), "[core]\n\tbare = true\n").unwrap();
+
+        assert_eq!(work_tree_of(&git_dir, git_dir.parent().unwrap()), Err(Error::BareRepository));
+    }
+
+    #[test]
+    fn work_tree_is_parent_of_git_dir() -> Result<(), Error> {
+        let root = env::temp_dir().join("work_tree_of_parent");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".git/config"), "[core]\n\tbare = false\n").unwrap();
+
+        let (git_dir, found_root) = find_repository_from(&root)?;
+        assert_eq!(work_tree_of(&git_dir, &found_root)?, root);
+        Ok(())
+    }
+
+    #[test]
+    fn find_git_dir_from_follows_git_file() -> anyhow::Result<()> {
+        let root = env::temp_dir().join("find_git_dir_from_git_file");
+        let _ = fs::remove_dir_all(&root);
+        let hash = "bd9dbf5aae1a3862dd1526723246b20206e5fc37";
+        fs::create_dir_all(root.join("modules/sub/objects").join(&hash[..2]))?;
+        fs::write(root.join("modules/sub/objects").join(&hash[..2]).join(&hash[2..]), b"")?;
+        fs::create_dir_all(root.join("sub/src"))?;
+        fs::write(root.join("sub/.git"), "gitdir: ../modules/sub\n")?;
+
+        let (git_dir, work_tree) = find_repository_from(&root.join("sub/src"))?;
+        assert_eq!(git_dir, root.join("sub/../modules/sub"));
+        assert_eq!(work_tree, root.join("sub"));
+        assert_eq!(crate::catfile::resolve_hash_in(&git_dir, &hash[..7])?, hash);
+        Ok(())
+    }
+
+    #[test]
+    fn find_git_dir_from_is_error_with_malformed_git_file() {
+        let root = env::temp_dir().join("find_git_dir_from_malformed_git_file");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(".git"), "not a pointer\n").unwrap();
+
+        let expected_error = Err(Error::InvalidGitFile(root.join(".git").display().to_string()));
+        assert_eq!(find_git_dir_from(&root), expected_error);
+    }
+}
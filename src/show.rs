@@ -0,0 +1,234 @@
+use crate::catfile;
+use crate::commit;
+use crate::diff;
+use crate::diff::TreeChange;
+use crate::log;
+use crate::refs;
+use crate::repo;
+use crate::tag;
+use crate::tree;
+
+const ABBREV: usize = 7;
+const GITLINK_MODE: &str = "160000";
+const STAT_WIDTH: usize = 80;
+
+#[derive(Debug, PartialEq, Eq)]
+struct FileStat {
+    path: String,
+    lines: Option<(usize, usize)>,
+    old_size: usize,
+    new_size: usize,
+}
+
+fn blob_content(mode: Option<&String>, hash: Option<&String>) -> anyhow::Result<Vec<u8>> {
+    match (mode, hash) {
+        (Some(mode), Some(hash)) if mode != GITLINK_MODE => catfile::read_blob(hash),
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn short_hash(hash: Option<&String>) -> String {
+    match hash {
+        Some(hash) => catfile::abbreviate(hash, ABBREV),
+        None => "0".repeat(ABBREV),
+    }
+}
+
+fn format_patch(change: &TreeChange, old_content: &[u8], new_content: &[u8]) -> String {
+    let path = &change.path;
+    let mut out = format!("diff --git a/{path} b/{path}\n");
+    let mut index_mode = String::new();
+    match (&change.old_mode, &change.new_mode) {
+        (None, Some(new)) => out.push_str(&format!("new file mode {new}\n")),
+        (Some(old), None) => out.push_str(&format!("deleted file mode {old}\n")),
+        (Some(old), Some(new)) if old != new => out.push_str(&format!("old mode {old}\nnew mode {new}\n")),
+        (Some(old), Some(_)) => index_mode = format!(" {old}"),
+        (None, None) => {}
+    }
+    if change.old_hash == change.new_hash {
+        return out;
+    }
+    out.push_str(&format!("index {}..{}{index_mode}\n", short_hash(change.old_hash.as_ref()), short_hash(change.new_hash.as_ref())));
+    let old_name = change.old_hash.as_ref().map_or("/dev/null".to_string(), |_| format!("a/{path}"));
+    let new_name = change.new_hash.as_ref().map_or("/dev/null".to_string(), |_| format!("b/{path}"));
+    if diff::is_binary(old_content) || diff::is_binary(new_content) {
+        out.push_str(&format!("Binary files {old_name} and {new_name} differ\n"));
+    } else if !old_content.is_empty() || !new_content.is_empty() {
+        out.push_str(&format!("--- {old_name}\n+++ {new_name}\n"));
+        out.push_str(&diff::unified_diff(old_content, new_content));
+    }
+    out
+}
+
+fn plural(count: usize, word: &str) -> String {
+    match count {
+        1 => format!("{count} {word}"),
+        _ => format!("{count} {word}s"),
+    }
+}
+
+fn graph_width(name_width: usize, count_width: usize, max_change: usize) -> usize {
+    let fixed = count_width + 6;
+    if name_width + fixed + max_change <= STAT_WIDTH {
+        return max_change;
+    }
+    let graph_width = max_change.min((STAT_WIDTH * 3 / 8).saturating_sub(fixed).max(6));
+    STAT_WIDTH.saturating_sub(fixed + name_width).max(graph_width)
+}
+
+fn scale_linear(count: usize, width: usize, max_change: usize) -> usize {
+    match count {
+        0 => 0,
+        count => 1 + count * (width - 1) / max_change,
+    }
+}
+
+fn scale_graph(added: usize, removed: usize, width: usize, max_change: usize) -> (usize, usize) {
+    if width >= max_change {
+        return (added, removed);
+    }
+    let mut total = scale_linear(added + removed, width, max_change);
+    if total < 2 && added > 0 && removed > 0 {
+        total = 2;
+    }
+    if added < removed {
+        let added = scale_linear(added, width, max_change);
+        (added, total - added)
+    } else {
+        let removed = scale_linear(removed, width, max_change);
+        (total - removed, removed)
+    }
+}
+
+fn format_stat(stats: &[FileStat]) -> String {
+    let width = stats.iter().map(|stat| stat.path.len()).max().unwrap_or(0);
+    let count_width = stats
+        .iter()
+        .map(|stat| stat.lines.map_or("Bin".len(), |(insertions, deletions)| (insertions + deletions).to_string().len()))
+        .max()
+        .unwrap_or(0);
+    let max_change = stats.iter().filter_map(|stat| stat.lines).map(|(added, removed)| added + removed).max().unwrap_or(0);
+    let graph_width = graph_width(width, count_width, max_change);
+    let mut out = String::new();
+    let (mut insertions, mut deletions) = (0, 0);
+    for FileStat { path, lines, old_size, new_size } in stats {
+        match lines {
+            Some((added, removed)) => {
+                let (plus, minus) = scale_graph(*added, *removed, graph_width, max_change);
+                let graph = format!("{}{}", "+".repeat(plus), "-".repeat(minus));
+                let line = format!(" {path:<width$} | {:>count_width$} {graph}", added + removed);
+                out.push_str(line.trim_end());
+                out.push('\n');
+                insertions += added;
+                deletions += removed;
+            }
+            None => out.push_str(&format!(" {path:<width$} | Bin {old_size} -> {new_size} bytes\n")),
+        }
+    }
+    out.push_str(&format!(" {} changed", plural(stats.len(), "file")));
+    if insertions > 0 || deletions == 0 {
+        out.push_str(&format!(", {}(+)", plural(insertions, "insertion")));
+    }
+    if deletions > 0 {
+        out.push_str(&format!(", {}(-)", plural(deletions, "deletion")));
+    }
+    out.push('\n');
+    out
+}
+
+pub fn show(rev: Option<&str>, stat: bool) -> anyhow::Result<()> {
+    let hash = match rev {
+        Some(rev) => tag::peel_tag(&refs::rev_parse(rev)?)?,
+        None => log::head_commit()?,
+    };
+    let commit = commit::parse_commit_with(&hash, &repo::shallow_commits()?)?;
+    print!("{}", log::format_commit(&hash, &commit));
+
+    let parent_entries = match commit.parents.first() {
+        Some(parent) => tree::read_tree(&commit::parse_commit(parent)?.tree)?,
+        None => Vec::new(),
+    };
+    let changes = diff::diff_trees(&parent_entries, &tree::read_tree(&commit.tree)?)?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+    println!();
+    let mut stats = Vec::new();
+    for change in &changes {
+        let old_content = blob_content(change.old_mode.as_ref(), change.old_hash.as_ref())?;
+        let new_content = blob_content(change.new_mode.as_ref(), change.new_hash.as_ref())?;
+        if !stat {
+            print!("{}", format_patch(change, &old_content, &new_content));
+            continue;
+        }
+        let binary = diff::is_binary(&old_content) || diff::is_binary(&new_content);
+        stats.push(FileStat {
+            path: change.path.clone(),
+            lines: (!binary).then(|| diff::line_counts(&old_content, &new_content)),
+            old_size: old_content.len(),
+            new_size: new_content.len(),
+        });
+    }
+    if stat {
+        print!("{}", format_stat(&stats));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::status::ChangeKind;
+
+    fn change(path: &str, old: Option<(&str, &str)>, new: Option<(&str, &str)>, status: ChangeKind) -> TreeChange {
+        TreeChange {
+            path: path.to_string(),
+            old_mode: old.map(|(mode, _)| mode.to_string()),
+            new_mode: new.map(|(mode, _)| mode.to_string()),
+            old_hash: old.map(|(_, hash)| hash.repeat(40)),
+            new_hash: new.map(|(_, hash)| hash.repeat(40)),
+            status,
+        }
+    }
+
+    #[test]
+    fn format_patch_for_new_file_compares_against_dev_null() {
+        let change = change("a.txt", None, Some(("100644", "1")), ChangeKind::Added);
+        let expected_patch = "diff --git a/a.txt b/a.txt\nnew file mode 100644\nindex 0000000..1111111\n\
+            --- /dev/null\n+++ b/a.txt\n@@ -0,0 +1 @@\n+hello\n";
+
+        assert_eq!(format_patch(&change, b"", b"hello\n"), expected_patch);
+    }
+
+    #[test]
+    fn format_patch_for_mode_change_omits_content() {
+        let change = change("run.sh", Some(("100644", "1")), Some(("100755", "1")), ChangeKind::Modified);
+
+        assert_eq!(format_patch(&change, b"x\n", b"x\n"), "diff --git a/run.sh b/run.sh\nold mode 100644\nnew mode 100755\n");
+    }
+
+    #[test]
+    fn format_stat_sums_insertions_and_deletions() {
+        let stat = |path: &str, lines, new_size| FileStat { path: path.to_string(), lines, old_size: 0, new_size };
+        let stats = [stat("a.txt", Some((2, 1)), 0), stat("longer.txt", Some((0, 10)), 0), stat("image.png", None, 12)];
+        let expected_stat = " a.txt      |   3 ++-\n longer.txt |  10 ----------\n image.png  | Bin 0 -> 12 bytes\n \
+            3 files changed, 2 insertions(+), 11 deletions(-)\n";
+
+        assert_eq!(format_stat(&stats), expected_stat);
+    }
+
+    #[test]
+    fn format_stat_scales_large_changes_to_fit_the_line() {
+        let stat = |path: &str, lines| FileStat { path: path.to_string(), lines, old_size: 0, new_size: 0 };
+        let stats = [stat("a.txt", Some((50000, 0))), stat("b.txt", Some((0, 60))), stat("c.txt", Some((30000, 20000)))];
+        let expected_stat = format!(
+            " a.txt | 50000 {}\n b.txt |    60 -\n c.txt | 50000 {}{}\n \
+            3 files changed, 80000 insertions(+), 20060 deletions(-)\n",
+            "+".repeat(64),
+            "+".repeat(38),
+            "-".repeat(26),
+        );
+
+        assert_eq!(format_stat(&stats), expected_stat);
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use crate::catfile;
+use crate::color;
+use crate::color::{Color, ColorChoice};
+use crate::commit;
+use crate::index;
+use crate::refs;
+use crate::repo;
+use crate::tree;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl ChangeKind {
+    fn label(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "new file:",
+            ChangeKind::Modified => "modified:",
+            ChangeKind::Deleted => "deleted:",
+        }
+    }
+
+    fn code(&self) -> char {
+        match self {
+            ChangeKind::Added => 'A',
+            ChangeKind::Modified => 'M',
+            ChangeKind::Deleted => 'D',
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        match self {
+            ChangeKind::Added => Some(Color::Green),
+            ChangeKind::Modified => None,
+            ChangeKind::Deleted => Some(Color::Red),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Status {
+    pub staged: Vec<(ChangeKind, String)>,
+    pub unstaged: Vec<(ChangeKind, String)>,
+    pub untracked: Vec<String>,
+}
+
+fn diff_maps(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Vec<(ChangeKind, String)> {
+    let paths: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    paths
+        .into_iter()
+        .filter_map(|path| match (old.get(path), new.get(path)) {
+            (None, Some(_)) => Some((ChangeKind::Added, path.clone())),
+            (Some(_), None) => Some((ChangeKind::Deleted, path.clone())),
+            (Some(old_hash), Some(new_hash)) if old_hash != new_hash => Some((ChangeKind::Modified, path.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+fn untracked_path(path: &str, index: &BTreeMap<String, String>) -> String {
+    for (position, _) in path.match_indices('/') {
+        let directory = &path[..=position];
+        if !index.keys().any(|tracked| tracked.starts_with(directory)) {
+            return directory.to_string();
+        }
+    }
+    path.to_string()
+}
+
+fn compare(
+    head: &BTreeMap<String, String>,
+    index: &BTreeMap<String, String>,
+    worktree_paths: &BTreeSet<String>,
+    worktree_hashes: &BTreeMap<String, String>,
+) -> Status {
+    let unstaged = diff_maps(index, worktree_hashes)
+        .into_iter()
+        .filter(|(kind, _)| *kind != ChangeKind::Added)
+        .collect();
+    let untracked: BTreeSet<String> = worktree_paths
+        .iter()
+        .filter(|path| !index.contains_key(*path))
+        .map(|path| untracked_path(path, index))
+        .collect();
+    Status {
+        staged: diff_maps(head, index),
+        unstaged,
+        untracked: untracked.into_iter().collect(),
+    }
+}
+
+fn format_section(title: &str, lines: &[String]) -> String {
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut output = format!("{title}:\n");
+    for line in lines {
+        output.push_str(&format!("\t{line}\n"));
+    }
+    output.push('\n');
+    output
+}
+
+fn format_changes(changes: &[(ChangeKind, String)], use_color: bool) -> Vec<String> {
+    changes
+        .iter()
+        .map(|(kind, path)| {
+            let line = format!("{:<12}{path}", kind.label());
+            match kind.color() {
+                Some(kind_color) => color::paint(&line, kind_color, use_color),
+                None => line,
+            }
+        })
+        .collect()
+}
+
+fn format_status(branch: Option<&str>, status: &Status, use_color: bool) -> String {
+    let mut output = match branch {
+        Some(branch) => format!("On branch {branch}\n"),
+        None => "HEAD detached\n".to_string(),
+    };
+    output.push_str(&format_section("Changes to be committed", &format_changes(&status.staged, use_color)));
+    output.push_str(&format_section("Changes not staged for commit", &format_changes(&status.unstaged, use_color)));
+    output.push_str(&format_section("Untracked files", &status.untracked));
+    if status == &Status::default() {
+        output.push_str("nothing to commit, working tree clean\n");
+    }
+    output
+}
+
+fn format_porcelain(status: &Status) -> String {
+    let mut codes: BTreeMap<&str, (char, char)> = BTreeMap::new();
+    for (kind, path) in &status.staged {
+        codes.entry(path).or_insert((' ', ' ')).0 = kind.code();
+    }
+    for (kind, path) in &status.unstaged {
+        codes.entry(path).or_insert((' ', ' ')).1 = kind.code();
+    }
+    let mut output = String::new();
+    for (path, (staged, unstaged)) in codes {
+        output.push_str(&format!("{staged}{unstaged} {path}\n"));
+    }
+    for path in &status.untracked {
+        output.push_str(&format!("?? {path}\n"));
+    }
+    output
+}
+
+fn collect_worktree(root: &Path, dir: &Path, paths: &mut BTreeSet<String>) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_worktree(root, &path, paths)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            paths.insert(relative.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn head_files() -> anyhow::Result<BTreeMap<String, String>> {
+    match refs::resolve_ref("HEAD") {
+        Ok(hash) => tree::flatten_tree(&commit::parse_commit(&hash)?.tree),
+        Err(err) if matches!(err.downcast_ref(), Some(refs::Error::RefNotFound(_))) => Ok(BTreeMap::new()),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn collect_status() -> anyhow::Result<Status> {
+    let work_tree = repo::work_tree()?;
+    let head = head_files()?;
+    let index: BTreeMap<String, String> = index::read_index()?
+        .into_iter()
+        .map(|entry| (entry.path, hex::encode(entry.hash)))
+        .collect();
+    let mut worktree_paths = BTreeSet::new();
+    collect_worktree(&work_tree, &work_tree, &mut worktree_paths)?;
+    let mut worktree_hashes = BTreeMap::new();
+    for path in index.keys().filter(|path| worktree_paths.contains(*path)) {
+        let file = work_tree.join(path);
+        worktree_hashes.insert(path.clone(), catfile::hash_file(&file.to_string_lossy())?);
+    }
+    Ok(compare(&head, &index, &worktree_paths, &worktree_hashes))
+}
+
+pub fn status(porcelain: bool, color_choice: ColorChoice) -> anyhow::Result<()> {
+    let status = collect_status()?;
+    if porcelain {
+        print!("{}", format_porcelain(&status));
+        return Ok(());
+    }
+    let head = refs::symbolic_target("HEAD")?;
+    let branch = head.as_deref().map(|head| head.strip_prefix("refs/heads/").unwrap_or(head));
+    print!("{}", format_status(branch, &status, color::enabled(color_choice)));
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn map(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries.iter().map(|(path, hash)| (path.to_string(), hash.to_string())).collect()
+    }
+
+    fn set(paths: &[&str]) -> BTreeSet<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn compare_reports_staged_changes() {
+        let head = map(&[("kept", "1"), ("changed", "1"), ("removed", "1")]);
+        let index = map(&[("kept", "1"), ("changed", "2"), ("added", "1")]);
+        let worktree = map(&[("kept", "1"), ("changed", "2"), ("added", "1")]);
+        let expected_staged = vec![
+            (ChangeKind::Added, "added".to_string()),
+            (ChangeKind::Modified, "changed".to_string()),
+            (ChangeKind::Deleted, "removed".to_string()),
+        ];
+
+        let status = compare(&head, &index, &set(&["kept", "changed", "added"]), &worktree);
+        assert_eq!(status.staged, expected_staged);
+        assert!(status.unstaged.is_empty());
+        assert!(status.untracked.is_empty());
+    }
+
+    #[test]
+    fn compare_reports_unstaged_changes() {
+        let index = map(&[("edited", "1"), ("missing", "1")]);
+        let worktree = map(&[("edited", "2")]);
+        let expected_unstaged = vec![
+            (ChangeKind::Modified, "edited".to_string()),
+            (ChangeKind::Deleted, "missing".to_string()),
+        ];
+
+        let status = compare(&index, &index, &set(&["edited"]), &worktree);
+        assert_eq!(status.unstaged, expected_unstaged);
+    }
+
+    #[test]
+    fn compare_collapses_untracked_directories() {
+        let index = map(&[("src/main.rs", "1")]);
+        let paths = set(&["src/main.rs", "src/new.rs", "target/debug/app", "target/debug/other", "notes.txt"]);
+        let expected_untracked = vec!["notes.txt".to_string(), "src/new.rs".to_string(), "target/".to_string()];
+
+        let status = compare(&index, &index, &paths, &index);
+        assert_eq!(status.untracked, expected_untracked);
+    }
+
+    #[test]
+    fn format_status_groups_sections() {
+        let status = Status {
+            staged: vec![(ChangeKind::Added, "a.txt".to_string())],
+            unstaged: vec![(ChangeKind::Modified, "b.txt".to_string())],
+            untracked: vec!["c.txt".to_string()],
+        };
+        let expected_output = "On branch main\n\
+            Changes to be committed:\n\tnew file:   a.txt\n\n\
+            Changes not staged for commit:\n\tmodified:   b.txt\n\n\
+            Untracked files:\n\tc.txt\n\n";
+
+        let output = format_status(Some("main"), &status, false);
+        assert_eq!(output, expected_output);
+    }
+
+    #[test]
+    fn format_changes_colors_added_and_deleted_files() {
+        let changes = vec![
+            (ChangeKind::Added, "a.txt".to_string()),
+            (ChangeKind::Modified, "b.txt".to_string()),
+            (ChangeKind::Deleted, "c.txt".to_string()),
+        ];
+        let expected_lines = ["\x1b[32mnew file:   a.txt\x1b[m", "modified:   b.txt", "\x1b[31mdeleted:    c.txt\x1b[m"];
+
+        assert_eq!(format_changes(&changes, true), expected_lines);
+    }
+
+    #[test]
+    fn format_status_with_clean_tree() {
+        let expected_output = "On branch main\nnothing to commit, working tree clean\n";
+
+        let output = format_status(Some("main"), &Status::default(), false);
+        assert_eq!(output, expected_output);
+    }
+
+    #[test]
+    fn format_porcelain_combines_staged_and_unstaged_columns() {
+        let status = Status {
+            staged: vec![
+                (ChangeKind::Added, "added".to_string()),
+                (ChangeKind::Added, "added-then-edited".to_string()),
+                (ChangeKind::Added, "added-then-removed".to_string()),
+                (ChangeKind::Modified, "both".to_string()),
+                (ChangeKind::Deleted, "removed".to_string()),
+                (ChangeKind::Modified, "staged".to_string()),
+            ],
+            unstaged: vec![
+                (ChangeKind::Modified, "added-then-edited".to_string()),
+                (ChangeKind::Deleted, "added-then-removed".to_string()),
+                (ChangeKind::Modified, "both".to_string()),
+                (ChangeKind::Deleted, "missing".to_string()),
+                (ChangeKind::Modified, "unstaged".to_string()),
+            ],
+            untracked: vec!["new/".to_string(), "notes.txt".to_string()],
+        };
+        let expected_output = "A  added\n\
+            AM added-then-edited\n\
+            AD added-then-removed\n\
+            MM both\n\
+            \x20D missing\n\
+            D  removed\n\
+            M  staged\n\
+            \x20M unstaged\n\
+            ?? new/\n\
+            ?? notes.txt\n";
+
+        assert_eq!(format_porcelain(&status), expected_output);
+    }
+
+    #[test]
+    fn format_porcelain_with_clean_tree_is_empty() {
+        assert_eq!(format_porcelain(&Status::default()), "");
+    }
+}
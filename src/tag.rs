@@ -0,0 +1,255 @@
+use std::path::Path;
+use thiserror::Error;
+use crate::catfile;
+use crate::catfile::{ObjectType, ParseMode};
+use crate::commit::{Identity, Timestamp};
+use crate::refs;
+use crate::repo;
+use crate::Verbosity;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("tag '{0}' already exists")]
+    TagExists(String),
+    #[error("annotated tags need a message: use -m")]
+    MissingMessage,
+    #[error("not a valid object name: there are no commits to tag")]
+    NoCommits,
+    #[error("tag '{0}' not found")]
+    TagNotFound(String),
+    #[error("malformed tag object")]
+    MalformedTag,
+    #[error("not a tag object: {0}")]
+    NotATag(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Tag {
+    pub object: String,
+    pub object_type: ObjectType,
+    pub name: String,
+    pub tagger: Option<String>,
+    pub message: String,
+}
+
+fn tag_content(object: &str, object_type: ObjectType, name: &str, tagger: &str, message: &str) -> String {
+    let mut content = format!("object {object}\ntype {object_type}\ntag {name}\ntagger {tagger}\n\n{message}");
+    if !message.ends_with('\n') {
+        content.push('\n');
+    }
+    content
+}
+
+fn parse_tag_content(content: &[u8]) -> Result<Tag, Error> {
+    let content = String::from_utf8_lossy(content);
+    let (headers, message) = content.split_once("\n\n").unwrap_or((&content, ""));
+    let mut object = None;
+    let mut object_type = None;
+    let mut name = None;
+    let mut tagger = None;
+    for line in headers.lines() {
+        match line.split_once(' ') {
+            Some(("object", value)) => object = Some(value.to_string()),
+            Some(("type", value)) => object_type = Some(ObjectType::from_bytes(value.as_bytes()).map_err(|_|Error::MalformedTag)?),
+            Some(("tag", value)) => name = Some(value.to_string()),
+            Some(("tagger", value)) => tagger = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Ok(Tag {
+        object: object.ok_or(Error::MalformedTag)?,
+        object_type: object_type.ok_or(Error::MalformedTag)?,
+        name: name.ok_or(Error::MalformedTag)?,
+        tagger,
+        message: message.to_string(),
+    })
+}
+
+pub fn parse_tag(hash: &str) -> anyhow::Result<Tag> {
+    let (object_type, content) = catfile::read_object(hash, ParseMode::Strict)?;
+    if object_type != ObjectType::Tag {
+        return Err(Error::NotATag(hash.to_string()).into());
+    }
+    Ok(parse_tag_content(&content)?)
+}
+
+fn peel_with<F>(hash: &str, read_object: F) -> anyhow::Result<String>
+where
+    F: Fn(&str) -> anyhow::Result<(ObjectType, Vec<u8>)>,
+{
+    let mut hash = hash.to_string();
+    loop {
+        let (object_type, content) = read_object(&hash)?;
+        if object_type != ObjectType::Tag {
+            return Ok(hash);
+        }
+        hash = parse_tag_content(&content)?.object;
+    }
+}
+
+pub(crate) fn peel_tag_in(git_dir: &Path, hash: &str) -> anyhow::Result<String> {
+    peel_with(hash, |hash| catfile::read_object_in(git_dir, hash, ParseMode::Strict))
+}
+
+pub fn peel_tag(hash: &str) -> anyhow::Result<String> {
+    peel_with(hash, |hash| catfile::read_object(hash, ParseMode::Strict))
+}
+
+fn check_new_tag(git_dir: &Path, name: &str) -> Result<String, Error> {
+    let ref_name = format!("refs/tags/{name}");
+    if refs::resolve_ref_in(git_dir, &ref_name).is_ok() {
+        return Err(Error::TagExists(name.to_string()));
+    }
+    Ok(ref_name)
+}
+
+fn create_lightweight_tag_in(git_dir: &Path, name: &str) -> anyhow::Result<()> {
+    let ref_name = check_new_tag(git_dir, name)?;
+    let hash = refs::resolve_ref_in(git_dir, "HEAD").map_err(|_|Error::NoCommits)?;
+    refs::update_ref_in(git_dir, &ref_name, &hash, &format!("tag: tagging {name}"))?;
+    Ok(())
+}
+
+fn create_annotated_tag_in(git_dir: &Path, name: &str, message: &str) -> anyhow::Result<()> {
+    let ref_name = check_new_tag(git_dir, name)?;
+    let target = refs::resolve_ref_in(git_dir, "HEAD").map_err(|_|Error::NoCommits)?;
+    let target_type = catfile::object_type_in(git_dir, &target)?;
+    let tagger = Identity::committer().signature(Timestamp::committer()?);
+    let content = tag_content(&target, target_type, name, &tagger, message);
+    let hash = catfile::write_object_content_in(git_dir, ObjectType::Tag, content.as_bytes())?;
+    refs::update_ref_in(git_dir, &ref_name, &hash, &format!("tag: tagging {name}"))?;
+    Ok(())
+}
+
+fn delete_tag_in(git_dir: &Path, name: &str) -> anyhow::Result<String> {
+    let ref_name = format!("refs/tags/{name}");
+    if refs::resolve_ref_in(git_dir, &ref_name).is_err() {
+        return Err(Error::TagNotFound(name.to_string()).into());
+    }
+    refs::delete_ref_in(git_dir, &ref_name)
+}
+
+pub fn tag(name: &str, message: Option<&str>, annotated: bool, delete: bool, verbosity: Verbosity) -> anyhow::Result<()> {
+    let git_dir = repo::git_dir()?;
+    if delete {
+        let hash = delete_tag_in(&git_dir, name)?;
+        if !verbosity.is_quiet() {
+            println!("Deleted tag '{name}' (was {})", catfile::abbreviate(&hash, 7));
+        }
+        return Ok(());
+    }
+    match (message, annotated) {
+        (Some(message), _) => create_annotated_tag_in(&git_dir, name, message),
+        (None, true) => Err(Error::MissingMessage.into()),
+        (None, false) => create_lightweight_tag_in(&git_dir, name),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const HASH: &str = "ab3e94d6b7ec38af9d3dee5b424a13a292742c4c";
+
+    fn git_dir_with_head(name: &str) -> PathBuf {
+        let git_dir = std::env::temp_dir().join(name).join(".git");
+        let _ = fs::remove_dir_all(&git_dir);
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(git_dir.join("refs/heads/main"), format!("{HASH}\n")).unwrap();
+        git_dir
+    }
+
+    #[test]
+    fn tag_content_hashes_like_git() {
+        let tagger = "Jane Doe <jane@example.com> 1700000000 +0000";
+        let expected_hash = "413c915d30ddbad82a9ed800c968d6a735372fc9";
+
+        let content = tag_content(HASH, ObjectType::Commit, "v1.0", tagger, "release 1.0");
+        let payload = catfile::object_payload(ObjectType::Tag, content.as_bytes());
+        assert_eq!(catfile::ObjectFormat::Sha1.hash(&payload), expected_hash);
+    }
+
+    #[test]
+    fn lightweight_tag_points_at_head_commit() -> anyhow::Result<()> {
+        let git_dir = git_dir_with_head("lightweight_tag_head_commit");
+
+        create_lightweight_tag_in(&git_dir, "v1.0")?;
+        assert_eq!(fs::read_to_string(git_dir.join("refs/tags/v1.0"))?, format!("{HASH}\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn lightweight_tag_is_error_when_tag_exists() -> anyhow::Result<()> {
+        let git_dir = git_dir_with_head("lightweight_tag_exists");
+        create_lightweight_tag_in(&git_dir, "v1.0")?;
+
+        let actual_result = create_lightweight_tag_in(&git_dir, "v1.0");
+        let error = actual_result.unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error, Error::TagExists("v1.0".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn lightweight_tag_is_error_when_tag_is_only_packed() {
+        let git_dir = git_dir_with_head("lightweight_tag_packed_exists");
+        fs::write(git_dir.join("packed-refs"), format!("{HASH} refs/tags/v1.0\n")).unwrap();
+
+        let actual_result = create_lightweight_tag_in(&git_dir, "v1.0");
+        let error = actual_result.unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error, Error::TagExists("v1.0".to_string()));
+        assert!(!git_dir.join("refs/tags/v1.0").exists());
+    }
+
+    #[test]
+    fn delete_tag_removes_ref() -> anyhow::Result<()> {
+        let git_dir = git_dir_with_head("delete_tag_ref");
+        create_lightweight_tag_in(&git_dir, "v1.0")?;
+
+        assert_eq!(delete_tag_in(&git_dir, "v1.0")?, HASH);
+        let error = delete_tag_in(&git_dir, "v1.0").unwrap_err().downcast::<Error>().unwrap();
+        assert_eq!(error, Error::TagNotFound("v1.0".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_tag_content_reads_headers_and_message() -> Result<(), Error> {
+        let tagger = "Jane Doe <jane@example.com> 1700000000 +0000";
+        let expected_tag = Tag {
+            object: HASH.to_string(),
+            object_type: ObjectType::Commit,
+            name: "v1.0".to_string(),
+            tagger: Some(tagger.to_string()),
+            message: "release 1.0\n".to_string(),
+        };
+
+        let tag = parse_tag_content(tag_content(HASH, ObjectType::Commit, "v1.0", tagger, "release 1.0").as_bytes())?;
+        assert_eq!(tag, expected_tag);
+        Ok(())
+    }
+
+    #[test]
+    fn peel_tag_follows_nested_tags() -> anyhow::Result<()> {
+        let _lock = crate::repo::ENV_LOCK.lock().unwrap();
+        let git_dir = std::env::temp_dir().join("peel_tag_nested");
+        let _ = fs::remove_dir_all(&git_dir);
+        fs::create_dir_all(git_dir.join("objects"))?;
+        let tagger = "Jane Doe <jane@example.com> 1700000000 +0000";
+        std::env::set_var("GIT_DIR", &git_dir);
+
+        let result = (|| {
+            let blob = catfile::write_object_content(ObjectType::Blob, b"payload")?;
+            let inner = catfile::write_object_content(ObjectType::Tag, tag_content(&blob, ObjectType::Blob, "inner", tagger, "inner").as_bytes())?;
+            let outer = catfile::write_object_content(ObjectType::Tag, tag_content(&inner, ObjectType::Tag, "outer", tagger, "outer").as_bytes())?;
+            Ok::<_, anyhow::Error>((peel_tag(&outer)?, peel_tag(&blob)?, blob))
+        })();
+        std::env::remove_var("GIT_DIR");
+
+        let (peeled, unchanged, blob) = result?;
+        assert_eq!(peeled, blob);
+        assert_eq!(unchanged, blob);
+        Ok(())
+    }
+}
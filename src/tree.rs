@@ -0,0 +1,607 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::BufRead;
+use std::path::Path;
+use thiserror::Error;
+use crate::catfile;
+use crate::catfile::{ObjectFormat, ObjectType, ParseMode};
+use crate::index::IndexEntry;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("malformed tree object")]
+    MalformedTree,
+    #[error("not a tree object: {0}")]
+    NotATree(String),
+    #[error("bad ls-tree format: {0}")]
+    BadFormat(String),
+    #[error("input format error: {0}")]
+    MalformedInput(String),
+    #[error("path '{0}' does not exist")]
+    PathNotFound(String),
+    #[error("path '{0}' is not a directory")]
+    NotADirectory(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum FormatPart {
+    Literal(String),
+    ObjectMode,
+    ObjectType,
+    ObjectName,
+    Path,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub mode: String,
+    pub name: String,
+    pub hash: String,
+}
+
+impl TreeEntry {
+    pub fn object_type(&self) -> ObjectType {
+        match self.mode.as_str() {
+            "40000" => ObjectType::Tree,
+            "160000" => ObjectType::Commit,
+            _ => ObjectType::Blob,
+        }
+    }
+}
+
+pub(crate) fn parse_tree(content: &[u8], format: ObjectFormat) -> Result<Vec<TreeEntry>, Error> {
+    let hash_len = format.raw_len();
+    let mut entries = Vec::new();
+    let mut rest = content;
+    while !rest.is_empty() {
+        let space = rest.iter().position(|&b| b == b' ').ok_or(Error::MalformedTree)?;
+        let nul = rest.iter().position(|&b| b == 0).ok_or(Error::MalformedTree)?;
+        if nul < space || rest.len() < nul + 1 + hash_len {
+            return Err(Error::MalformedTree);
+        }
+        let mode = std::str::from_utf8(&rest[..space]).map_err(|_|Error::MalformedTree)?;
+        let name = std::str::from_utf8(&rest[space + 1..nul]).map_err(|_|Error::MalformedTree)?;
+        entries.push(TreeEntry {
+            mode: mode.to_string(),
+            name: name.to_string(),
+            hash: hex::encode(&rest[nul + 1..nul + 1 + hash_len]),
+        });
+        rest = &rest[nul + 1 + hash_len..];
+    }
+    Ok(entries)
+}
+
+pub(crate) fn format_entry(entry: &TreeEntry, name_only: bool) -> String {
+    if name_only {
+        return entry.name.clone();
+    }
+    format!("{:0>6} {} {}\t{}", entry.mode, entry.object_type(), entry.hash, entry.name)
+}
+
+fn parse_format(format: &str) -> Result<Vec<FormatPart>, Error> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut rest = format;
+    while let Some(position) = rest.find('%') {
+        literal.push_str(&rest[..position]);
+        rest = &rest[position..];
+        if let Some(after) = rest.strip_prefix("%%") {
+            literal.push('%');
+            rest = after;
+            continue;
+        }
+        let end = rest.find(')').filter(|_| rest.starts_with("%(")).ok_or_else(|| Error::BadFormat(rest.to_string()))?;
+        let part = match &rest[2..end] {
+            "objectmode" => FormatPart::ObjectMode,
+            "objecttype" => FormatPart::ObjectType,
+            "objectname" => FormatPart::ObjectName,
+            "path" => FormatPart::Path,
+            _ => return Err(Error::BadFormat(rest[..=end].to_string())),
+        };
+        if !literal.is_empty() {
+            parts.push(FormatPart::Literal(std::mem::take(&mut literal)));
+        }
+        parts.push(part);
+        rest = &rest[end + 1..];
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        parts.push(FormatPart::Literal(literal));
+    }
+    Ok(parts)
+}
+
+fn apply_format(parts: &[FormatPart], entry: &TreeEntry) -> String {
+    parts
+        .iter()
+        .map(|part| match part {
+            FormatPart::Literal(text) => text.clone(),
+            FormatPart::ObjectMode => format!("{:0>6}", entry.mode),
+            FormatPart::ObjectType => entry.object_type().to_string(),
+            FormatPart::ObjectName => entry.hash.clone(),
+            FormatPart::Path => entry.name.clone(),
+        })
+        .collect()
+}
+
+pub(crate) fn compare_entries(a: (&str, &str), b: (&str, &str)) -> Ordering {
+    let key = |(mode, name): (&str, &str)| {
+        let mut key = name.as_bytes().to_vec();
+        if mode == "40000" {
+            key.push(b'/');
+        }
+        key
+    };
+    key(a).cmp(&key(b))
+}
+
+fn sort_entries<S: AsRef<str>>(entries: &mut [(S, String, Vec<u8>)]) {
+    entries.sort_by(|a, b| compare_entries((a.0.as_ref(), &a.1), (b.0.as_ref(), &b.1)));
+}
+
+fn tree_content<S: AsRef<str>>(entries: &[(S, String, Vec<u8>)]) -> Vec<u8> {
+    let mut content = Vec::new();
+    for (mode, name, hash) in entries {
+        content.extend_from_slice(format!("{} {name}\0", mode.as_ref()).as_bytes());
+        content.extend_from_slice(hash);
+    }
+    content
+}
+
+fn raw_hash(hash: &str) -> anyhow::Result<Vec<u8>> {
+    hex::decode(hash).map_err(|_|catfile::Error::InvalidObjectHash(hash.to_string()).into())
+}
+
+/// Only the owner's execute bit makes a file 100755, matching git.
+#[cfg(unix)]
+pub(crate) fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o100 != 0
+}
+
+#[cfg(unix)]
+pub(crate) fn file_mode(metadata: &fs::Metadata) -> &'static str {
+    if is_executable(metadata) { "100755" } else { "100644" }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_mode(_metadata: &fs::Metadata) -> &'static str {
+    "100644"
+}
+
+fn write_dir(dir: &Path) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut dir_entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    dir_entries.sort_by_key(|entry| entry.file_name());
+
+    let mut entries = Vec::new();
+    for entry in dir_entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name == ".git" {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if let Some(hash) = write_dir(&entry.path())? {
+                entries.push(("40000", name, hash));
+            }
+        } else if file_type.is_symlink() {
+            let hash = catfile::write_object_content(ObjectType::Blob, &catfile::read_worktree_file(&entry.path())?)?;
+            entries.push(("120000", name, raw_hash(&hash)?));
+        } else {
+            let hash = catfile::write_object_content(ObjectType::Blob, &fs::read(entry.path())?)?;
+            entries.push((file_mode(&entry.metadata()?), name, raw_hash(&hash)?));
+        }
+    }
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    sort_entries(&mut entries);
+    let hash = catfile::write_object_content(ObjectType::Tree, &tree_content(&entries))?;
+    raw_hash(&hash).map(Some)
+}
+
+pub fn write_tree(dir: &Path) -> anyhow::Result<Vec<u8>> {
+    match write_dir(dir)? {
+        Some(hash) => Ok(hash),
+        None => raw_hash(&catfile::write_object_content(ObjectType::Tree, &[])?),
+    }
+}
+
+fn index_tree<F>(entries: &[(String, &str, &[u8])], write: &mut F) -> anyhow::Result<Vec<u8>>
+where
+    F: FnMut(&[u8]) -> anyhow::Result<String>,
+{
+    let mut tree = Vec::new();
+    let mut rest = entries;
+    while let Some((mode, path, hash)) = rest.first() {
+        match path.split_once('/') {
+            None => {
+                tree.push((mode.clone(), path.to_string(), hash.to_vec()));
+                rest = &rest[1..];
+            }
+            Some((dir, _)) => {
+                let len = rest.iter().take_while(|(_, path, _)| path.strip_prefix(dir).is_some_and(|rest| rest.starts_with('/'))).count();
+                let children: Vec<_> = rest[..len].iter().map(|(mode, path, hash)| (mode.clone(), &path[dir.len() + 1..], *hash)).collect();
+                tree.push(("40000".to_string(), dir.to_string(), index_tree(&children, write)?));
+                rest = &rest[len..];
+            }
+        }
+    }
+    sort_entries(&mut tree);
+    raw_hash(&write(&tree_content(&tree))?)
+}
+
+pub fn write_tree_from_index(entries: &[IndexEntry]) -> anyhow::Result<Vec<u8>> {
+    let mut staged: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry.stage() == 0)
+        .map(|entry| (format!("{:o}", entry.mode), entry.path.as_str(), entry.hash.as_slice()))
+        .collect();
+    staged.sort_by(|a, b| a.1.as_bytes().cmp(b.1.as_bytes()));
+    index_tree(&staged, &mut |content| catfile::write_object_content(ObjectType::Tree, content))
+}
+
+fn parse_mktree_line(line: &str, format: ObjectFormat) -> Result<(String, String, Vec<u8>), Error> {
+    let malformed = || Error::MalformedInput(line.to_string());
+    let (info, name) = line.split_once('\t').ok_or_else(malformed)?;
+    let mut fields = info.split(' ');
+    let (Some(mode), Some(object_type), Some(hash), None) = (fields.next(), fields.next(), fields.next(), fields.next()) else {
+        return Err(malformed());
+    };
+    let mode = mode.trim_start_matches('0');
+    let entry = TreeEntry { mode: mode.to_string(), name: name.to_string(), hash: hash.to_string() };
+    if name.is_empty() || name.contains('/') || entry.object_type().as_str() != object_type {
+        return Err(malformed());
+    }
+    let hash = hex::decode(hash).map_err(|_|malformed())?;
+    if hash.len() != format.raw_len() {
+        return Err(malformed());
+    }
+    Ok((entry.mode, entry.name, hash))
+}
+
+pub fn mktree<R: BufRead>(input: R) -> anyhow::Result<String> {
+    let format = ObjectFormat::current();
+    let mut entries = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        if !line.is_empty() {
+            entries.push(parse_mktree_line(&line, format)?);
+        }
+    }
+    sort_entries(&mut entries);
+    catfile::write_object_content(ObjectType::Tree, &tree_content(&entries))
+}
+
+pub fn read_tree(hash: &str) -> anyhow::Result<Vec<TreeEntry>> {
+    let (object_type, content) = catfile::read_object(hash, ParseMode::Strict)?;
+    if object_type != ObjectType::Tree {
+        return Err(Error::NotATree(hash.to_string()).into());
+    }
+    Ok(parse_tree(&content, ObjectFormat::current())?)
+}
+
+fn flatten_into(hash: &str, prefix: &str, files: &mut BTreeMap<String, String>) -> anyhow::Result<()> {
+    for entry in read_tree(hash)? {
+        let path = format!("{prefix}{}", entry.name);
+        match entry.object_type() {
+            ObjectType::Tree => flatten_into(&entry.hash, &format!("{path}/"), files)?,
+            _ => {
+                files.insert(path, entry.hash);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn flatten_tree(hash: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut files = BTreeMap::new();
+    flatten_into(hash, "", &mut files)?;
+    Ok(files)
+}
+
+fn lookup_path_with<F>(tree: &str, path: &str, read: &F) -> anyhow::Result<TreeEntry>
+where
+    F: Fn(&str) -> anyhow::Result<Vec<TreeEntry>>,
+{
+    let mut current = TreeEntry { mode: "40000".to_string(), name: String::new(), hash: tree.to_string() };
+    let mut walked = String::new();
+    for component in path.split('/').filter(|component| !component.is_empty()) {
+        if current.object_type() != ObjectType::Tree {
+            return Err(Error::NotADirectory(walked).into());
+        }
+        if !walked.is_empty() {
+            walked.push('/');
+        }
+        walked.push_str(component);
+        current = read(&current.hash)?
+            .into_iter()
+            .find(|entry| entry.name == component)
+            .ok_or_else(|| Error::PathNotFound(walked.clone()))?;
+    }
+    Ok(current)
+}
+
+pub fn lookup_path(tree: &str, path: &str) -> anyhow::Result<TreeEntry> {
+    lookup_path_with(tree, path, &read_tree)
+}
+
+fn walk_tree<F>(hash: &str, prefix: &str, recurse: bool, show_trees: bool, read: &F, out: &mut Vec<TreeEntry>) -> anyhow::Result<()>
+where
+    F: Fn(&str) -> anyhow::Result<Vec<TreeEntry>>,
+{
+    for mut entry in read(hash)? {
+        entry.name = format!("{prefix}{}", entry.name);
+        if recurse && entry.object_type() == ObjectType::Tree {
+            let subtree = entry.hash.clone();
+            let subtree_prefix = format!("{}/", entry.name);
+            if show_trees {
+                out.push(entry);
+            }
+            walk_tree(&subtree, &subtree_prefix, recurse, show_trees, read, out)?;
+        } else {
+            out.push(entry);
+        }
+    }
+    Ok(())
+}
+
+pub fn ls_tree(hash: &str, recurse: bool, show_trees: bool, name_only: bool, abbrev: Option<usize>, format: Option<&str>) -> anyhow::Result<()> {
+    let format = format.map(parse_format).transpose()?;
+    let mut entries = Vec::new();
+    walk_tree(hash, "", recurse, show_trees, &read_tree, &mut entries)?;
+    for mut entry in entries {
+        if let Some(n) = abbrev {
+            entry.hash = catfile::abbreviate(&entry.hash, n);
+        }
+        match &format {
+            Some(format) => println!("{}", apply_format(format, &entry)),
+            None => println!("{}", format_entry(&entry, name_only)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample_tree() -> Vec<u8> {
+        let mut content = Vec::new();
+        content.extend_from_slice(b"100644 b.txt\0");
+        content.extend_from_slice(&[0xaa; 20]);
+        content.extend_from_slice(b"40000 a\0");
+        content.extend_from_slice(&[0x01; 20]);
+        content
+    }
+
+    #[test]
+    fn parse_tree_keeps_stored_order() -> Result<(), Error> {
+        let expected_entries = vec![
+            TreeEntry { mode: "100644".to_string(), name: "b.txt".to_string(), hash: "aa".repeat(20) },
+            TreeEntry { mode: "40000".to_string(), name: "a".to_string(), hash: "01".repeat(20) },
+        ];
+
+        let entries = parse_tree(&sample_tree(), ObjectFormat::Sha1)?;
+        assert_eq!(entries, expected_entries);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_tree_succeeds_with_empty_tree() -> Result<(), Error> {
+        let entries = parse_tree(&[], ObjectFormat::Sha1)?;
+        assert!(entries.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_tree_is_error_with_truncated_hash() {
+        let content = b"100644 b.txt\0\xaa\xaa";
+        let expected_error = Err(Error::MalformedTree);
+
+        let actual_result = parse_tree(content, ObjectFormat::Sha1);
+        assert_eq!(actual_result, expected_error);
+    }
+
+    #[test]
+    fn tree_content_hashes_like_git() -> anyhow::Result<()> {
+        let entries = [
+            ("100644", "a.txt".to_string(), raw_hash("45b983be36b73c0788dc9cbcb76cbb80fc7bb057")?),
+            ("40000", "d".to_string(), raw_hash("c956b7a48038a889b1c9257b5bcc1dd93186e362")?),
+        ];
+        let expected_hash = "0df530b3a655a943b379c67747b940b7199f6116";
+
+        let payload = catfile::object_payload(ObjectType::Tree, &tree_content(&entries));
+        assert_eq!(ObjectFormat::Sha1.hash(&payload), expected_hash);
+        Ok(())
+    }
+
+    #[test]
+    fn tree_content_hashes_like_git_with_sha256() -> anyhow::Result<()> {
+        let entries = [
+            ("100644", "a.txt".to_string(), raw_hash("96c18f0297e38d01f4b2dacddea4259aea6b2961eb0822bd2c0c3f6029030045")?),
+        ];
+        let expected_hash = "93ab4a225e0241d0f16c233d2afe4c6b0c7eea330e06a7c59696836a6f889129";
+
+        let payload = catfile::object_payload(ObjectType::Tree, &tree_content(&entries));
+        assert_eq!(ObjectFormat::Sha256.hash(&payload), expected_hash);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_tree_with_sha256_hashes() -> Result<(), Error> {
+        let entries = [("100644", "a.txt".to_string(), vec![0x02; 32])];
+        let expected_entries = vec![
+            TreeEntry { mode: "100644".to_string(), name: "a.txt".to_string(), hash: "02".repeat(32) },
+        ];
+
+        let parsed = parse_tree(&tree_content(&entries), ObjectFormat::Sha256)?;
+        assert_eq!(parsed, expected_entries);
+        Ok(())
+    }
+
+    #[test]
+    fn tree_content_round_trips_through_parse_tree() -> Result<(), Error> {
+        let entries = [("100644", "main.rs".to_string(), vec![0x01; 20])];
+        let expected_entries = vec![
+            TreeEntry { mode: "100644".to_string(), name: "main.rs".to_string(), hash: "01".repeat(20) },
+        ];
+
+        let parsed = parse_tree(&tree_content(&entries), ObjectFormat::Sha1)?;
+        assert_eq!(parsed, expected_entries);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_entry() {
+        let entry = TreeEntry { mode: "40000".to_string(), name: "src".to_string(), hash: "01".repeat(20) };
+        let expected_line = format!("040000 tree {}\tsrc", "01".repeat(20));
+
+        let line = format_entry(&entry, false);
+        assert_eq!(line, expected_line);
+    }
+
+    #[test]
+    fn test_format_entry_name_only() {
+        let entry = TreeEntry { mode: "100644".to_string(), name: "main.rs".to_string(), hash: "01".repeat(20) };
+        let expected_line = "main.rs";
+
+        let line = format_entry(&entry, true);
+        assert_eq!(line, expected_line);
+    }
+
+    fn nested_trees(hash: &str) -> anyhow::Result<Vec<TreeEntry>> {
+        let entry = |mode: &str, name: &str, hash: &str| TreeEntry { mode: mode.to_string(), name: name.to_string(), hash: hash.to_string() };
+        Ok(match hash {
+            "root" => vec![entry("100644", "Cargo.toml", "blob1"), entry("40000", "src", "src")],
+            "src" => vec![entry("40000", "bin", "bin"), entry("100644", "main.rs", "blob2")],
+            "bin" => vec![entry("100755", "tool.rs", "blob3")],
+            _ => return Err(Error::NotATree(hash.to_string()).into()),
+        })
+    }
+
+    #[test]
+    fn lookup_path_follows_components_to_the_leaf() -> anyhow::Result<()> {
+        assert_eq!(lookup_path_with("root", "src/bin/tool.rs", &nested_trees)?.hash, "blob3");
+        assert_eq!(lookup_path_with("root", "src/", &nested_trees)?.hash, "src");
+        assert_eq!(lookup_path_with("root", "", &nested_trees)?.hash, "root");
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_path_is_error_with_missing_or_non_tree_component() {
+        let missing = lookup_path_with("root", "src/lib.rs", &nested_trees).unwrap_err();
+        assert_eq!(missing.downcast_ref(), Some(&Error::PathNotFound("src/lib.rs".to_string())));
+
+        let through_blob = lookup_path_with("root", "src/main.rs/inner", &nested_trees).unwrap_err();
+        assert_eq!(through_blob.downcast_ref(), Some(&Error::NotADirectory("src/main.rs".to_string())));
+    }
+
+    fn walked_names(recurse: bool, show_trees: bool) -> anyhow::Result<Vec<String>> {
+        let mut entries = Vec::new();
+        walk_tree("root", "", recurse, show_trees, &nested_trees, &mut entries)?;
+        Ok(entries.into_iter().map(|entry| entry.name).collect())
+    }
+
+    #[test]
+    fn walk_tree_without_recurse_lists_top_level() -> anyhow::Result<()> {
+        assert_eq!(walked_names(false, false)?, ["Cargo.toml", "src"]);
+        Ok(())
+    }
+
+    #[test]
+    fn walk_tree_recurses_with_full_paths() -> anyhow::Result<()> {
+        assert_eq!(walked_names(true, false)?, ["Cargo.toml", "src/bin/tool.rs", "src/main.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn walk_tree_recurses_and_shows_trees() -> anyhow::Result<()> {
+        assert_eq!(walked_names(true, true)?, ["Cargo.toml", "src", "src/bin", "src/bin/tool.rs", "src/main.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn apply_format_substitutes_placeholders() -> Result<(), Error> {
+        let entry = TreeEntry { mode: "40000".to_string(), name: "src".to_string(), hash: "01".repeat(20) };
+        let expected_line = format!("040000 tree {} src 100%", "01".repeat(20));
+
+        let parts = parse_format("%(objectmode) %(objecttype) %(objectname) %(path) 100%%")?;
+        assert_eq!(apply_format(&parts, &entry), expected_line);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_format_is_error_with_unknown_placeholder() {
+        let expected_error = Err(Error::BadFormat("%(objectsize:padded)".to_string()));
+
+        assert_eq!(parse_format("%(path) %(objectsize:padded)"), expected_error);
+    }
+
+    #[test]
+    fn parse_mktree_line_reads_ls_tree_output() -> Result<(), Error> {
+        let line = format!("040000 tree {}\tsrc", "01".repeat(20));
+
+        let entry = parse_mktree_line(&line, ObjectFormat::Sha1)?;
+        assert_eq!(entry, ("40000".to_string(), "src".to_string(), vec![0x01; 20]));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_mktree_line_is_error_with_mismatched_type() {
+        let line = format!("100644 tree {}\ta.txt", "01".repeat(20));
+        let expected_error = Err(Error::MalformedInput(line.clone()));
+
+        assert_eq!(parse_mktree_line(&line, ObjectFormat::Sha1), expected_error);
+    }
+
+    #[test]
+    fn sort_entries_orders_directories_with_trailing_slash() -> anyhow::Result<()> {
+        let mut entries = vec![
+            ("40000", "foo".to_string(), raw_hash("4b825dc642cb6eb9a060e54bf8d69288fbee4904")?),
+            ("100644", "foo.txt".to_string(), raw_hash("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391")?),
+        ];
+        let expected_hash = "1e5fda10594d49d4a484454c512634e07e7604d0";
+
+        sort_entries(&mut entries);
+        let names: Vec<&str> = entries.iter().map(|(_, name, _)| name.as_str()).collect();
+        assert_eq!(names, ["foo.txt", "foo"]);
+        let payload = catfile::object_payload(ObjectType::Tree, &tree_content(&entries));
+        assert_eq!(ObjectFormat::Sha1.hash(&payload), expected_hash);
+        Ok(())
+    }
+
+    #[test]
+    fn index_tree_nests_directories_like_git() -> anyhow::Result<()> {
+        let hashes = [
+            ("a-b", raw_hash("bca70f35318f31dd1d1d1d2d2e64c19b880899ff")?),
+            ("a.txt", raw_hash("45b983be36b73c0788dc9cbcb76cbb80fc7bb057")?),
+            ("a/b", raw_hash("587be6b4c3f93f93c489c0111bba5596147a26cb")?),
+            ("src/bin/tool.rs", raw_hash("718f4d2ff533cf8ead8d3556cf43912bd245fbc4")?),
+            ("src/main.rs", raw_hash("28ce6a8b26aa170e1de65536fe8abe1832bd3242")?),
+        ];
+        let entries: Vec<_> = hashes.iter().map(|(path, hash)| ("100644".to_string(), *path, hash.as_slice())).collect();
+        let expected_hash = "7bb07d63e7940a5d00d397f4b5734c62ed95573a";
+
+        let hash = index_tree(&entries, &mut |content| Ok(ObjectFormat::Sha1.hash(&catfile::object_payload(ObjectType::Tree, content))))?;
+        assert_eq!(hex::encode(hash), expected_hash);
+        Ok(())
+    }
+
+    #[test]
+    fn write_tree_of_fresh_repo_is_empty_tree() -> anyhow::Result<()> {
+        let _lock = crate::repo::ENV_LOCK.lock().unwrap();
+        let work_tree = std::env::temp_dir().join("write_tree_fresh_repo");
+        let _ = fs::remove_dir_all(&work_tree);
+        fs::create_dir_all(work_tree.join(".git").join("objects"))?;
+        fs::create_dir_all(work_tree.join("empty").join("nested"))?;
+
+        std::env::set_var("GIT_DIR", work_tree.join(".git"));
+        let result = write_tree(&work_tree);
+        std::env::remove_var("GIT_DIR");
+
+        assert_eq!(hex::encode(result?), "4b825dc642cb6eb9a060e54bf8d69288fbee4904");
+        Ok(())
+    }
+}